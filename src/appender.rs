@@ -1,6 +1,224 @@
+use crate::fields::{EventValue, FieldsVisitor};
 use crate::pattern::Pattern;
+use chrono::{DateTime, Local};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{Level, Metadata};
+
+#[cfg(all(target_os = "android", feature = "android"))]
+pub mod android;
+#[cfg(feature = "audit")]
+pub mod audit;
+pub mod callsite_limit;
+pub mod circular;
+#[cfg(feature = "encryption")]
+pub mod encrypted;
+#[cfg(all(windows, feature = "etw"))]
+pub mod etw;
+pub mod fallback;
+pub mod file;
+#[cfg(all(target_os = "linux", feature = "journald"))]
+pub mod journald;
+pub mod json_file;
+pub mod non_blocking;
+#[cfg(all(target_vendor = "apple", feature = "oslog"))]
+pub mod oslog;
+pub mod ring_buffer;
+pub mod strip_ansi;
+pub mod target_patterns;
+pub mod writer;
+
+/// Values of the layer's resolvers, see
+/// [`ConfigurableLayer::with_resolver`](crate::ConfigurableLayer::with_resolver).
+pub(crate) trait ResolvedFields {
+    fn resolved_fields(&self) -> Vec<(&'static str, EventValue)>;
+}
+
+/// Event data handed to appenders alongside the rendered line.
+pub struct Record<'a> {
+    metadata: &'static Metadata<'static>,
+    level: Level,
+    time: Option<DateTime<Local>>,
+    fields: &'a FieldsVisitor,
+    resolvers: Option<&'a (dyn ResolvedFields + 'a)>,
+}
+
+impl<'a> Record<'a> {
+    pub fn new(metadata: &'static Metadata<'static>, fields: &'a FieldsVisitor) -> Self {
+        Self {
+            metadata,
+            level: *metadata.level(),
+            time: None,
+            fields,
+            resolvers: None,
+        }
+    }
+
+    /// Sets the time the event happened, as read from the layer's clock.
+    pub fn with_time(mut self, time: DateTime<Local>) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    pub(crate) fn with_resolvers(mut self, resolvers: &'a (dyn ResolvedFields + 'a)) -> Self {
+        self.resolvers = Some(resolvers);
+        self
+    }
+
+    /// Overrides the level, when the config remapped the level of the event.
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    pub fn metadata(&self) -> &'static Metadata<'static> {
+        self.metadata
+    }
+
+    /// Level of the event after remapping, see [`LayerConfig::remap_level`](crate::config::LayerConfig::remap_level).
+    pub fn level(&self) -> &Level {
+        &self.level
+    }
+
+    pub fn target(&self) -> &str {
+        self.metadata.target()
+    }
+
+    /// Time of the event, the same that patterns render; `None` for records not created
+    /// by the layer.
+    pub fn time(&self) -> Option<DateTime<Local>> {
+        self.time
+    }
+
+    pub fn fields(&self) -> &FieldsVisitor {
+        self.fields
+    }
+
+    /// Values of the layer's resolvers for names the event doesn't record itself. Resolvers
+    /// that haven't run for this event yet run now, so only appenders writing every field
+    /// (e.g. as JSON) should ask.
+    pub fn resolved_fields(&self) -> Vec<(&'static str, EventValue)> {
+        self.resolvers
+            .map(|i| i.resolved_fields())
+            .unwrap_or_default()
+    }
+}
+
+/// Owned copy of a [`Record`], for appenders and buffers writing the event later.
+pub(crate) struct OwnedRecord {
+    metadata: &'static Metadata<'static>,
+    level: Level,
+    time: Option<DateTime<Local>>,
+    fields: FieldsVisitor,
+}
+
+impl OwnedRecord {
+    pub(crate) fn new(record: &Record<'_>) -> Self {
+        Self {
+            metadata: record.metadata,
+            level: record.level,
+            time: record.time,
+            fields: record.fields.clone(),
+        }
+    }
+
+    /// Like [`new`](Self::new), also copying the values of the layer's resolvers into the
+    /// fields, which runs all of them.
+    pub(crate) fn with_resolved(record: &Record<'_>) -> Self {
+        let mut owned = Self::new(record);
+        for (name, value) in record.resolved_fields() {
+            owned.fields.insert_if_absent(name, value);
+        }
+
+        owned
+    }
+
+    pub(crate) fn metadata(&self) -> &'static Metadata<'static> {
+        self.metadata
+    }
+
+    pub(crate) fn record(&self) -> Record<'_> {
+        Record {
+            metadata: self.metadata,
+            level: self.level,
+            time: self.time,
+            fields: &self.fields,
+            resolvers: None,
+        }
+    }
+}
 
 pub trait Appender {
     fn pattern(&self) -> &Pattern;
-    fn write(&self, value: &str);
+    fn write(&self, value: &str) -> std::io::Result<()>;
+
+    /// Pattern used to render events from `target`.
+    fn pattern_for(&self, target: &str) -> &Pattern {
+        let _ = target;
+        self.pattern()
+    }
+
+    /// Writes a rendered event. Appenders forwarding to native logging facilities
+    /// override this to make use of the level, target and fields.
+    fn write_record(&self, record: &Record<'_>, value: &str) -> std::io::Result<()> {
+        let _ = record;
+        self.write(value)
+    }
+
+    /// Writes several rendered lines at once, each with the event it was rendered from if
+    /// there is one. Sinks where each write is a syscall should override this to issue a
+    /// single (vectored) write for the whole batch.
+    fn write_batch(&self, lines: &[(Option<&Record<'_>>, &str)]) -> std::io::Result<()> {
+        for (record, value) in lines {
+            match record {
+                Some(record) => self.write_record(record, value)?,
+                None => self.write(value)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Makes sure everything written so far has reached its destination.
+    fn flush(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    /// Writes out everything pending and releases what the appender holds, e.g. joins its
+    /// background thread or writes a file's footer, finishing before `deadline` where
+    /// possible. Called when the layer shuts down, possibly more than once.
+    fn close(&self, deadline: Instant) -> std::io::Result<()> {
+        let _ = deadline;
+        self.flush()
+    }
+}
+
+impl<A: Appender + ?Sized> Appender for Arc<A> {
+    fn pattern(&self) -> &Pattern {
+        (**self).pattern()
+    }
+
+    fn pattern_for(&self, target: &str) -> &Pattern {
+        (**self).pattern_for(target)
+    }
+
+    fn write(&self, value: &str) -> std::io::Result<()> {
+        (**self).write(value)
+    }
+
+    fn write_record(&self, record: &Record<'_>, value: &str) -> std::io::Result<()> {
+        (**self).write_record(record, value)
+    }
+
+    fn write_batch(&self, lines: &[(Option<&Record<'_>>, &str)]) -> std::io::Result<()> {
+        (**self).write_batch(lines)
+    }
+
+    fn flush(&self) -> std::io::Result<()> {
+        (**self).flush()
+    }
+
+    fn close(&self, deadline: Instant) -> std::io::Result<()> {
+        (**self).close(deadline)
+    }
 }
@@ -0,0 +1,85 @@
+use crate::appender::{Appender, Record};
+use crate::pattern::Pattern;
+use std::ffi::CString;
+use std::io;
+use std::os::raw::{c_char, c_int};
+use tracing::Level;
+
+/// Logcat rejects (pre API 26) or truncates longer tags.
+const MAX_TAG_LEN: usize = 23;
+const DEFAULT_TAG: &str = "tracing";
+
+const ANDROID_LOG_VERBOSE: c_int = 2;
+const ANDROID_LOG_DEBUG: c_int = 3;
+const ANDROID_LOG_INFO: c_int = 4;
+const ANDROID_LOG_WARN: c_int = 5;
+const ANDROID_LOG_ERROR: c_int = 6;
+
+#[link(name = "log")]
+extern "C" {
+    fn __android_log_write(prio: c_int, tag: *const c_char, text: *const c_char) -> c_int;
+}
+
+/// Writes events to logcat, using the event target as the log tag.
+pub struct AndroidAppender {
+    pattern: Pattern,
+}
+
+impl AndroidAppender {
+    pub fn new(pattern: Pattern) -> Self {
+        Self { pattern }
+    }
+
+    fn log(&self, prio: c_int, tag: &str, value: &str) -> io::Result<()> {
+        let tag = to_cstring(truncate(tag, MAX_TAG_LEN));
+        let text = to_cstring(value);
+
+        let ret = unsafe { __android_log_write(prio, tag.as_ptr(), text.as_ptr()) };
+        if ret < 0 {
+            Err(io::Error::from_raw_os_error(-ret))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Appender for AndroidAppender {
+    fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+
+    fn write(&self, value: &str) -> io::Result<()> {
+        self.log(ANDROID_LOG_INFO, DEFAULT_TAG, value)
+    }
+
+    fn write_record(&self, record: &Record<'_>, value: &str) -> io::Result<()> {
+        self.log(android_priority(record.level()), record.target(), value)
+    }
+}
+
+fn android_priority(level: &Level) -> c_int {
+    match *level {
+        Level::TRACE => ANDROID_LOG_VERBOSE,
+        Level::DEBUG => ANDROID_LOG_DEBUG,
+        Level::INFO => ANDROID_LOG_INFO,
+        Level::WARN => ANDROID_LOG_WARN,
+        Level::ERROR => ANDROID_LOG_ERROR,
+    }
+}
+
+fn truncate(v: &str, max: usize) -> &str {
+    if v.len() <= max {
+        return v;
+    }
+
+    let mut end = max;
+    while !v.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    &v[..end]
+}
+
+fn to_cstring(v: &str) -> CString {
+    CString::new(v.replace('\0', "\\0")).unwrap()
+}
@@ -0,0 +1,417 @@
+use crate::appender::{Appender, Record};
+use crate::pattern::Pattern;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Instant;
+
+const RECORD_TAG: &str = " [audit ";
+const CHECKPOINT_TAG: &str = "[audit-checkpoint ";
+
+#[derive(Clone, Default)]
+struct Chain {
+    seq: u64,
+    hash: [u8; 32],
+}
+
+impl Chain {
+    /// Appends `line`, already [escaped](escape), to the chain and returns it with its
+    /// sequence number and chained hash.
+    fn next(&mut self, line: &str) -> String {
+        self.seq += 1;
+        self.hash = chain_hash(&self.hash, self.seq, line);
+        format!("{}{}{} {}]", line, RECORD_TAG, self.seq, hex(&self.hash))
+    }
+
+    fn checkpoint(&self) -> String {
+        format!("{}{} {}]", CHECKPOINT_TAG, self.seq, hex(&self.hash))
+    }
+
+    /// Parses the `seq hash]` end of a tag.
+    fn parse(tag: &str) -> Option<Self> {
+        let (seq, hash) = tag.strip_suffix(']')?.split_once(' ')?;
+        if hash.len() != 64 || !hash.is_ascii() {
+            return None;
+        }
+
+        let mut bytes = [0; 32];
+        for (idx, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hash[idx * 2..idx * 2 + 2], 16).ok()?;
+        }
+
+        Some(Self {
+            seq: seq.parse().ok()?,
+            hash: bytes,
+        })
+    }
+}
+
+/// Makes the output of the inner appender tamper-evident.
+///
+/// Every line gets a sequence number and a SHA-256 hash over the previous hash and the line
+/// itself, so editing, removing or reordering lines breaks the chain. Line breaks and
+/// backslashes are escaped as `\n`, `\r` and `\\`, so each record stays on one line. With
+/// [`with_checkpoint_interval`](Self::with_checkpoint_interval) a checkpoint line carrying the
+/// current digest is written every N records, which can be shipped elsewhere as a witness.
+///
+/// Use [`verify`] to check a file written this way, [`verify_segment`] for files started
+/// by rotation, and [`resume`](Self::resume) to keep appending to one after a restart.
+pub struct AuditAppender<A: Appender> {
+    inner: A,
+    chain: Mutex<Chain>,
+    checkpoint_interval: Option<u64>,
+}
+
+impl<A: Appender> AuditAppender<A> {
+    pub fn new(inner: A) -> Self {
+        Self {
+            inner,
+            chain: Mutex::new(Chain::default()),
+            checkpoint_interval: None,
+        }
+    }
+
+    /// Continues the chain of the file at `path`, which `inner` appends to, so the file
+    /// still verifies as a whole after a restart. A missing file starts a new chain, a file
+    /// started by rotation continues from its last record.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidData`] if the existing lines don't verify as a
+    /// [segment](verify_segment).
+    pub fn resume<P: AsRef<Path>>(inner: A, path: P) -> io::Result<Self> {
+        let content = match std::fs::read_to_string(path.as_ref()) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+
+        let chain = replay(content.lines(), true).map_err(|line| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "audit chain of {} is broken at line {}",
+                    path.as_ref().display(),
+                    line
+                ),
+            )
+        })?;
+
+        Ok(Self {
+            inner,
+            chain: Mutex::new(chain),
+            checkpoint_interval: None,
+        })
+    }
+
+    pub fn with_checkpoint_interval(mut self, records: u64) -> Self {
+        self.checkpoint_interval = Some(records).filter(|i| *i > 0);
+        self
+    }
+
+    fn checkpoint_due(&self, chain: &Chain) -> bool {
+        self.checkpoint_interval
+            .is_some_and(|interval| chain.seq.is_multiple_of(interval))
+    }
+}
+
+impl<A: Appender> Appender for AuditAppender<A> {
+    fn pattern(&self) -> &Pattern {
+        self.inner.pattern()
+    }
+
+    fn pattern_for(&self, target: &str) -> &Pattern {
+        self.inner.pattern_for(target)
+    }
+
+    fn write(&self, value: &str) -> io::Result<()> {
+        let mut chain = self.chain.lock().unwrap();
+        // advanced on a copy, a failed write must not leave a gap in the chain
+        let mut next = chain.clone();
+        self.inner.write(&next.next(&escape(value)))?;
+        *chain = next;
+
+        if self.checkpoint_due(&chain) {
+            self.inner.write(&chain.checkpoint())?;
+        }
+
+        Ok(())
+    }
+
+    fn write_record(&self, record: &Record<'_>, value: &str) -> io::Result<()> {
+        // the lock is held while writing, so lines reach the inner appender in chain order
+        let mut chain = self.chain.lock().unwrap();
+        let mut next = chain.clone();
+        self.inner
+            .write_record(record, &next.next(&escape(value)))?;
+        *chain = next;
+
+        if self.checkpoint_due(&chain) {
+            self.inner.write(&chain.checkpoint())?;
+        }
+
+        Ok(())
+    }
+
+    fn write_batch(&self, lines: &[(Option<&Record<'_>>, &str)]) -> io::Result<()> {
+        let mut chain = self.chain.lock().unwrap();
+        let mut next = chain.clone();
+        let mut chained = Vec::with_capacity(lines.len());
+
+        for (record, value) in lines {
+            chained.push((*record, next.next(&escape(value))));
+
+            if self.checkpoint_due(&next) {
+                chained.push((None, next.checkpoint()));
+            }
+        }
+
+        let lines: Vec<_> = chained
+            .iter()
+            .map(|(record, value)| (*record, value.as_str()))
+            .collect();
+        self.inner.write_batch(&lines)?;
+        *chain = next;
+
+        Ok(())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    fn close(&self, deadline: Instant) -> io::Result<()> {
+        self.inner.close(deadline)
+    }
+}
+
+/// Checks lines written by an [`AuditAppender`], returning the 1-based number of the
+/// first line that breaks the chain.
+///
+/// Lines must be given without their line terminators, starting from the first record.
+/// Lines without an audit tag, like the headers and footers of [`FileOptions`], aren't part
+/// of the chain and are skipped.
+///
+/// [`FileOptions`]: crate::appender::file::FileOptions
+pub fn verify<'a, I: IntoIterator<Item = &'a str>>(lines: I) -> Result<(), usize> {
+    replay(lines, false).map(|_| ())
+}
+
+/// Like [`verify`], but the lines may start anywhere in the chain, e.g. in a file started by
+/// rotation. The first record or checkpoint is taken as is, unless it starts the chain, and
+/// the chain is checked from there.
+pub fn verify_segment<'a, I: IntoIterator<Item = &'a str>>(lines: I) -> Result<(), usize> {
+    replay(lines, true).map(|_| ())
+}
+
+/// Follows the chain through `lines`, returning its state after the last one. With
+/// `segment`, the chain continues from the first record or checkpoint instead of the start.
+fn replay<'a, I: IntoIterator<Item = &'a str>>(lines: I, segment: bool) -> Result<Chain, usize> {
+    let mut expected = Chain::default();
+    let mut anchored = !segment;
+
+    for (idx, line) in lines.into_iter().enumerate() {
+        let line_no = idx + 1;
+
+        let (value, tag) = if let Some(rest) = line.strip_prefix(CHECKPOINT_TAG) {
+            (None, rest)
+        } else if let Some(pos) = line.rfind(RECORD_TAG) {
+            (Some(&line[..pos]), &line[pos + RECORD_TAG.len()..])
+        } else {
+            continue;
+        };
+
+        if !anchored {
+            anchored = true;
+            let start = Chain::parse(tag).ok_or(line_no)?;
+
+            // the first record of the chain is checked all the same
+            if value.is_none() || start.seq != 1 {
+                expected = start;
+                continue;
+            }
+        }
+
+        let valid = match value {
+            Some(value) => expected.next(value) == line,
+            None => expected.checkpoint() == line,
+        };
+        if !valid {
+            return Err(line_no);
+        }
+    }
+
+    Ok(expected)
+}
+
+/// Escapes line breaks and backslashes, so a record can't span several lines.
+fn escape(value: &str) -> Cow<'_, str> {
+    if !value.contains(['\\', '\n', '\r']) {
+        return Cow::Borrowed(value);
+    }
+
+    let mut escaped = String::with_capacity(value.len() + 8);
+    for i in value.chars() {
+        match i {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            i => escaped.push(i),
+        }
+    }
+
+    Cow::Owned(escaped)
+}
+
+fn chain_hash(prev: &[u8; 32], seq: u64, line: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(prev);
+    hasher.update(seq.to_be_bytes());
+    hasher.update(line.as_bytes());
+    hasher.finalize().into()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::with_capacity(64), |mut acc, i| {
+        let _ = write!(acc, "{:02x}", i);
+        acc
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::appender::audit::{verify, verify_segment, AuditAppender};
+    use crate::appender::writer::WriterAppender;
+    use crate::appender::Appender;
+    use crate::pattern::Pattern;
+    use std::io;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+
+    struct FlakyAppender {
+        pattern: Pattern,
+        failing: AtomicBool,
+        lines: Mutex<Vec<String>>,
+    }
+
+    impl Appender for FlakyAppender {
+        fn pattern(&self) -> &Pattern {
+            &self.pattern
+        }
+
+        fn write(&self, value: &str) -> io::Result<()> {
+            if self.failing.load(Ordering::Relaxed) {
+                return Err(io::ErrorKind::StorageFull.into());
+            }
+
+            self.lines.lock().unwrap().push(value.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn detects_modified_lines() {
+        let appender = AuditAppender::new(WriterAppender::new(Pattern::new(vec![]), Vec::new()))
+            .with_checkpoint_interval(2);
+
+        appender.write("first").unwrap();
+        appender
+            .write_batch(&[(None, "second"), (None, "third")])
+            .unwrap();
+
+        let output = String::from_utf8(appender.inner.into_inner()).unwrap();
+        let mut lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(verify(lines.iter().copied()), Ok(()));
+
+        let tampered = lines[3].replacen("third", "3rd", 1);
+        lines[3] = &tampered;
+        assert_eq!(verify(lines.iter().copied()), Err(4));
+
+        lines.remove(0);
+        assert_eq!(verify(lines.iter().copied()), Err(1));
+    }
+
+    #[test]
+    fn verifies_multi_line_records_and_segments() {
+        let appender = AuditAppender::new(WriterAppender::new(Pattern::new(vec![]), Vec::new()));
+
+        appender.write("failed\n  at main.rs:3").unwrap();
+        appender.write("C:\\logs").unwrap();
+        appender.write("third").unwrap();
+
+        let output = String::from_utf8(appender.inner.into_inner()).unwrap();
+        let mut lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("failed\\n  at main.rs:3 [audit 1 "));
+        assert!(lines[1].starts_with("C:\\\\logs [audit 2 "));
+
+        // a header, and a file started by rotation after the first record
+        lines[0] = "# started";
+        assert_eq!(verify(lines.iter().copied()), Err(2));
+        assert_eq!(verify_segment(lines.iter().copied()), Ok(()));
+
+        let tampered = lines[2].replacen("third", "3rd", 1);
+        lines[2] = &tampered;
+        assert_eq!(verify_segment(lines.iter().copied()), Err(3));
+    }
+
+    #[test]
+    fn failed_writes_leave_no_gap() {
+        let appender = AuditAppender::new(FlakyAppender {
+            pattern: Pattern::new(vec![]),
+            failing: AtomicBool::new(false),
+            lines: Default::default(),
+        });
+
+        appender.write("first").unwrap();
+        appender.inner.failing.store(true, Ordering::Relaxed);
+        assert!(appender.write("lost").is_err());
+        assert!(appender
+            .write_batch(&[(None, "lost"), (None, "too")])
+            .is_err());
+        appender.inner.failing.store(false, Ordering::Relaxed);
+        appender.write("second").unwrap();
+
+        let lines = appender.inner.lines.lock().unwrap();
+        assert_eq!(verify(lines.iter().map(String::as_str)), Ok(()));
+    }
+
+    #[test]
+    fn resumes_chain_of_existing_file() {
+        use crate::appender::file::FileOptions;
+        use std::fs::OpenOptions;
+
+        let path = std::env::temp_dir().join(format!(
+            "tracing-configurable-audit-{}.log",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let open = || {
+            let mut options = OpenOptions::new();
+            options.create(true).append(true);
+            let file = FileOptions::default().open(&path, options).unwrap();
+            WriterAppender::new(Pattern::new(vec![]), file)
+        };
+
+        AuditAppender::resume(open(), &path)
+            .unwrap()
+            .write("before restart")
+            .unwrap();
+        AuditAppender::resume(open(), &path)
+            .unwrap()
+            .write("after restart")
+            .unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().count(), 2);
+        assert_eq!(verify(content.lines()), Ok(()));
+
+        std::fs::write(&path, content.replacen("before", "during", 1)).unwrap();
+        let error = AuditAppender::resume(open(), &path).err().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
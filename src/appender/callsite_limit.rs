@@ -0,0 +1,170 @@
+use crate::appender::{Appender, Record};
+use crate::fields::FieldsVisitor;
+use crate::pattern::Pattern;
+use std::collections::HashMap;
+use std::io;
+use std::sync::Mutex;
+use std::time::Instant;
+use tracing::callsite::Identifier;
+
+/// Writes only the first `limit` events of each callsite, e.g. for deprecation warnings or
+/// per-item errors in large batch loops.
+///
+/// The last event written is followed by a note that further occurrences are suppressed,
+/// written as a record with the level and target of the event.
+/// Lines written without a record (and so without a callsite) are passed through.
+pub struct CallsiteLimitAppender<A: Appender> {
+    inner: A,
+    limit: usize,
+    counts: Mutex<HashMap<Identifier, usize>>,
+}
+
+impl<A: Appender> CallsiteLimitAppender<A> {
+    pub fn new(inner: A, limit: usize) -> Self {
+        Self {
+            inner,
+            limit: limit.max(1),
+            counts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Forgets the counts, e.g. when the next batch starts.
+    pub fn reset(&self) {
+        self.counts.lock().unwrap().clear();
+    }
+}
+
+impl<A: Appender> Appender for CallsiteLimitAppender<A> {
+    fn pattern(&self) -> &Pattern {
+        self.inner.pattern()
+    }
+
+    fn pattern_for(&self, target: &str) -> &Pattern {
+        self.inner.pattern_for(target)
+    }
+
+    fn write(&self, value: &str) -> io::Result<()> {
+        self.inner.write(value)
+    }
+
+    fn write_record(&self, record: &Record<'_>, value: &str) -> io::Result<()> {
+        let count = {
+            let mut counts = self.counts.lock().unwrap();
+            let count = counts.entry(record.metadata().callsite()).or_default();
+            *count = count.saturating_add(1);
+            *count
+        };
+
+        if count > self.limit {
+            return Ok(());
+        }
+
+        self.inner.write_record(record, value)?;
+
+        if count == self.limit {
+            let fields = FieldsVisitor::with_message(format!(
+                "further occurrences of {} suppressed",
+                record.metadata().name()
+            ));
+            let note = Record {
+                fields: &fields,
+                ..*record
+            };
+            let value = format!("{}: {}", record.target(), fields.message());
+            self.inner.write_record(&note, &value)?;
+        }
+
+        Ok(())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    fn close(&self, deadline: Instant) -> io::Result<()> {
+        self.inner.close(deadline)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::appender::callsite_limit::CallsiteLimitAppender;
+    use crate::appender::json_file::JsonAppender;
+    use crate::testing::{CaptureAppender, TestConfig};
+    use crate::ConfigurableLayer;
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+    use tracing::{info, warn, Level};
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::registry;
+
+    #[test]
+    fn limits_each_callsite() {
+        let capture = CaptureAppender::new();
+        let appender = Arc::new(CallsiteLimitAppender::new(capture.clone(), 2));
+        let subscriber = registry().with(ConfigurableLayer::new(TestConfig::new(appender.clone())));
+
+        tracing::subscriber::with_default(subscriber, || {
+            for item in 0..5 {
+                warn!(item, "deprecated");
+                if item == 0 {
+                    info!("once");
+                }
+            }
+        });
+
+        let messages = capture
+            .events()
+            .into_iter()
+            .map(|i| i.message)
+            .collect::<Vec<_>>();
+
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0], "deprecated");
+        assert_eq!(messages[1], "once");
+        assert_eq!(messages[2], "deprecated");
+        assert!(messages[3].contains("further occurrences"));
+        assert_eq!(capture.events()[3].level, Some(Level::WARN));
+
+        appender.reset();
+        tracing::subscriber::with_default(
+            registry().with(ConfigurableLayer::new(TestConfig::new(appender))),
+            || warn!("after reset"),
+        );
+        assert_eq!(capture.events().len(), 5);
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writes_note_as_record() {
+        let buf = SharedBuf::default();
+        let appender = CallsiteLimitAppender::new(JsonAppender::new(buf.clone()), 1);
+        let subscriber = registry().with(ConfigurableLayer::new(TestConfig::new(appender)));
+
+        tracing::subscriber::with_default(subscriber, || {
+            for item in 0..3 {
+                warn!(target: "my_app::batch", item, "skipped");
+            }
+        });
+
+        let content = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].contains(
+            r#""level":"WARN","target":"my_app::batch","message":"further occurrences of "#
+        ));
+        assert!(lines[1].ends_with(r#" suppressed"}"#));
+    }
+}
@@ -0,0 +1,171 @@
+use crate::appender::file::{FileOptions, LogFile};
+use crate::appender::{Appender, Record};
+use crate::pattern::Pattern;
+use std::fs::OpenOptions;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+struct State {
+    file: LogFile,
+    len: u64,
+}
+
+/// Keeps the log in a single file that never grows past `max_size` bytes.
+///
+/// Once a line wouldn't fit, the file is rewritten to keep only the newest half of its
+/// lines, so it always starts at a line boundary and can be read with ordinary tools.
+/// Meant for devices with little disk space where rotating into several files isn't an option.
+pub struct CircularFileAppender {
+    pattern: Pattern,
+    path: PathBuf,
+    max_size: u64,
+    state: Mutex<State>,
+}
+
+impl CircularFileAppender {
+    pub fn new<P: AsRef<Path>>(pattern: Pattern, path: P, max_size: u64) -> io::Result<Self> {
+        Self::with_options(pattern, path, max_size, FileOptions::default())
+    }
+
+    pub fn with_options<P: AsRef<Path>>(
+        pattern: Pattern,
+        path: P,
+        max_size: u64,
+        options: FileOptions,
+    ) -> io::Result<Self> {
+        let mut open_options = OpenOptions::new();
+        open_options
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false);
+        // the file is kept below `max_size` by compacting it instead
+        let file = options
+            .without_rotation()
+            .open(path.as_ref(), open_options)?;
+        let len = file.len()?;
+
+        let appender = Self {
+            pattern,
+            path: path.as_ref().to_path_buf(),
+            max_size: max_size.max(2),
+            state: Mutex::new(State { file, len }),
+        };
+
+        // the cap may have been lowered since the file was written
+        if len > appender.max_size {
+            appender.compact(&mut appender.state.lock().unwrap(), 0)?;
+        }
+
+        Ok(appender)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Drops the oldest lines until `incoming` more bytes fit into half of the cap.
+    fn compact(&self, state: &mut State, incoming: u64) -> io::Result<()> {
+        let mut content = Vec::with_capacity(state.len as usize);
+        state.file.seek(SeekFrom::Start(0))?;
+        state.file.read_to_end(&mut content)?;
+
+        let keep = (self.max_size / 2).saturating_sub(incoming) as usize;
+        let mut start = content.len().saturating_sub(keep);
+        if start > 0 && content[start - 1] != b'\n' {
+            start = match content[start..].iter().position(|i| *i == b'\n') {
+                Some(pos) => start + pos + 1,
+                None => content.len(),
+            };
+        }
+
+        state.file.set_len(0)?;
+        state.file.seek(SeekFrom::Start(0))?;
+        state.file.write_all(&content[start..])?;
+        state.len = (content.len() - start) as u64;
+
+        Ok(())
+    }
+
+    fn write_line(&self, state: &mut State, value: &str) -> io::Result<()> {
+        let mut value = value;
+        if value.len() as u64 + 1 > self.max_size {
+            // a single line larger than the whole file keeps its beginning
+            let mut end = (self.max_size - 1) as usize;
+            while !value.is_char_boundary(end) {
+                end -= 1;
+            }
+            value = &value[..end];
+        }
+
+        if !state.file.is_open() {
+            // opening may have written a header
+            state.file.get()?;
+            state.len = state.file.len()?;
+        }
+
+        let incoming = value.len() as u64 + 1;
+        if state.len + incoming > self.max_size {
+            self.compact(state, incoming)?;
+        }
+
+        state.file.write_all(value.as_bytes())?;
+        state.file.write_all(b"\n")?;
+        state.len += incoming;
+
+        Ok(())
+    }
+}
+
+impl Appender for CircularFileAppender {
+    fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+
+    fn write(&self, value: &str) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        self.write_line(&mut state, value)
+    }
+
+    fn write_batch(&self, lines: &[(Option<&Record<'_>>, &str)]) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        for (_, value) in lines {
+            self.write_line(&mut state, value)?;
+        }
+
+        Ok(())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.state.lock().unwrap().file.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::appender::circular::CircularFileAppender;
+    use crate::appender::Appender;
+    use crate::pattern::Pattern;
+
+    #[test]
+    fn keeps_newest_lines_within_cap() {
+        let path = std::env::temp_dir().join(format!(
+            "tracing-configurable-circular-{}.log",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let appender = CircularFileAppender::new(Pattern::new(vec![]), &path, 64).unwrap();
+        for i in 0..100 {
+            appender.write(&format!("line {:03}", i)).unwrap();
+        }
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(content.len() <= 64);
+        assert!(content.starts_with("line "));
+        assert!(content.ends_with("line 099\n"));
+    }
+}
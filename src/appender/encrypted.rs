@@ -0,0 +1,128 @@
+use crate::appender::{Appender, Record};
+use crate::pattern::Pattern;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::io;
+use std::time::Instant;
+
+const NONCE_LEN: usize = 12;
+
+/// Encrypts every rendered line with AES-256-GCM before handing it to the inner appender.
+///
+/// Each line is written as base64 of a fresh random nonce followed by the ciphertext, so
+/// lines stay independent and the file can be read back with [`decrypt_line`].
+pub struct EncryptedAppender<A: Appender> {
+    inner: A,
+    cipher: Aes256Gcm,
+}
+
+impl<A: Appender> EncryptedAppender<A> {
+    pub fn new(inner: A, key: &[u8; 32]) -> Self {
+        Self {
+            inner,
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)),
+        }
+    }
+
+    fn encrypt(&self, value: &str) -> io::Result<String> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, value.as_bytes())
+            .map_err(|_| io::Error::other("failed to encrypt record"))?;
+
+        let mut data = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&ciphertext);
+
+        Ok(STANDARD.encode(data))
+    }
+}
+
+impl<A: Appender> Appender for EncryptedAppender<A> {
+    fn pattern(&self) -> &Pattern {
+        self.inner.pattern()
+    }
+
+    fn pattern_for(&self, target: &str) -> &Pattern {
+        self.inner.pattern_for(target)
+    }
+
+    fn write(&self, value: &str) -> io::Result<()> {
+        self.inner.write(&self.encrypt(value)?)
+    }
+
+    fn write_record(&self, record: &Record<'_>, value: &str) -> io::Result<()> {
+        self.inner.write_record(record, &self.encrypt(value)?)
+    }
+
+    fn write_batch(&self, lines: &[(Option<&Record<'_>>, &str)]) -> io::Result<()> {
+        let values = lines
+            .iter()
+            .map(|(_, i)| self.encrypt(i))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let lines: Vec<_> = lines
+            .iter()
+            .zip(&values)
+            .map(|((record, _), value)| (*record, value.as_str()))
+            .collect();
+        self.inner.write_batch(&lines)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    fn close(&self, deadline: Instant) -> io::Result<()> {
+        self.inner.close(deadline)
+    }
+}
+
+/// Decrypts a line written by an [`EncryptedAppender`] with the same key.
+pub fn decrypt_line(key: &[u8; 32], line: &str) -> io::Result<String> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+    let data = STANDARD
+        .decode(line.trim_end())
+        .map_err(|_| invalid("record is not valid base64"))?;
+
+    if data.len() < NONCE_LEN {
+        return Err(invalid("record is too short"));
+    }
+
+    let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+    let plaintext = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| invalid("failed to decrypt record"))?;
+
+    String::from_utf8(plaintext).map_err(|_| invalid("record is not valid utf-8"))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::appender::encrypted::{decrypt_line, EncryptedAppender};
+    use crate::appender::writer::WriterAppender;
+    use crate::appender::Appender;
+    use crate::pattern::Pattern;
+
+    #[test]
+    fn round_trips_lines() {
+        let key = [7u8; 32];
+        let appender =
+            EncryptedAppender::new(WriterAppender::new(Pattern::new(vec![]), Vec::new()), &key);
+
+        appender.write("card=4111").unwrap();
+        appender.write("card=4111").unwrap();
+
+        let output = String::from_utf8(appender.inner.into_inner()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert!(!output.contains("4111"));
+        assert_ne!(lines[0], lines[1]);
+        assert_eq!(decrypt_line(&key, lines[0]).unwrap(), "card=4111");
+        assert!(decrypt_line(&[0u8; 32], lines[1]).is_err());
+    }
+}
@@ -0,0 +1,211 @@
+use crate::appender::{Appender, Record};
+use crate::fields::EventValue;
+use crate::pattern::Pattern;
+use std::io;
+use tracing::Level;
+use windows_sys::core::GUID;
+use windows_sys::Win32::System::Diagnostics::Etw::{
+    EventProviderSetTraits, EventRegister, EventSetInformation, EventUnregister,
+    EventWriteTransfer, EVENT_DATA_DESCRIPTOR, EVENT_DATA_DESCRIPTOR_0, EVENT_DATA_DESCRIPTOR_0_0,
+    EVENT_DESCRIPTOR,
+};
+
+const CHANNEL_TRACELOGGING: u8 = 11;
+
+const DESCRIPTOR_TYPE_EVENT_METADATA: u8 = 1;
+const DESCRIPTOR_TYPE_PROVIDER_METADATA: u8 = 2;
+
+const IN_UNICODESTRING: u8 = 1;
+const IN_INT64: u8 = 9;
+const IN_UINT64: u8 = 10;
+const IN_DOUBLE: u8 = 12;
+const IN_BOOL32: u8 = 13;
+
+/// Writes events as TraceLogging (self-describing ETW) events.
+///
+/// Every event carries the rendered line as `message`, the event target as `target`
+/// and one field per recorded event value, so WPA/PerfView can show them as columns.
+pub struct EtwAppender {
+    pattern: Pattern,
+    handle: u64,
+    provider_metadata: Vec<u8>,
+}
+
+impl EtwAppender {
+    pub fn new<N: AsRef<str>>(
+        pattern: Pattern,
+        provider_name: N,
+        provider_id: u128,
+    ) -> io::Result<Self> {
+        let mut provider_metadata = Vec::new();
+        push_sized(&mut provider_metadata, |buf| {
+            buf.extend_from_slice(provider_name.as_ref().as_bytes());
+            buf.push(0);
+        });
+
+        let id = GUID::from_u128(provider_id);
+        let mut handle = 0;
+        let status = unsafe { EventRegister(&id, None, std::ptr::null(), &mut handle) };
+        if status != 0 {
+            return Err(io::Error::from_raw_os_error(status as i32));
+        }
+
+        unsafe {
+            EventSetInformation(
+                handle,
+                EventProviderSetTraits,
+                provider_metadata.as_ptr().cast(),
+                provider_metadata.len() as u32,
+            );
+        }
+
+        Ok(Self {
+            pattern,
+            handle,
+            provider_metadata,
+        })
+    }
+
+    fn emit(
+        &self,
+        level: &Level,
+        name: &str,
+        value: &str,
+        record: Option<&Record>,
+    ) -> io::Result<()> {
+        let mut fields = vec![("message", IN_UNICODESTRING, utf16(value))];
+
+        if let Some(record) = record {
+            fields.push(("target", IN_UNICODESTRING, utf16(record.target())));
+
+            for (key, values) in record.fields().values() {
+                for v in values {
+                    let field = match v {
+                        EventValue::I64(v) => (key, IN_INT64, v.to_le_bytes().to_vec()),
+                        EventValue::U64(v) => (key, IN_UINT64, v.to_le_bytes().to_vec()),
+                        EventValue::F64(v) => (key, IN_DOUBLE, v.to_le_bytes().to_vec()),
+                        EventValue::Bool(v) => (key, IN_BOOL32, (*v as u32).to_le_bytes().to_vec()),
+                        v => (key, IN_UNICODESTRING, utf16(&v.to_string())),
+                    };
+
+                    fields.push(field);
+                }
+            }
+        }
+
+        let mut event_metadata = Vec::new();
+        push_sized(&mut event_metadata, |buf| {
+            buf.push(0); // tags
+            buf.extend_from_slice(name.as_bytes());
+            buf.push(0);
+
+            for (name, ty, _) in &fields {
+                buf.extend_from_slice(name.as_bytes());
+                buf.push(0);
+                buf.push(*ty);
+            }
+        });
+
+        let mut data = Vec::with_capacity(fields.len() + 2);
+        data.push(descriptor(
+            &self.provider_metadata,
+            DESCRIPTOR_TYPE_PROVIDER_METADATA,
+        ));
+        data.push(descriptor(&event_metadata, DESCRIPTOR_TYPE_EVENT_METADATA));
+        data.extend(fields.iter().map(|(_, _, v)| descriptor(v, 0)));
+
+        let event = EVENT_DESCRIPTOR {
+            Id: 0,
+            Version: 0,
+            Channel: CHANNEL_TRACELOGGING,
+            Level: etw_level(level),
+            Opcode: 0,
+            Task: 0,
+            Keyword: 0,
+        };
+
+        let status = unsafe {
+            EventWriteTransfer(
+                self.handle,
+                &event,
+                std::ptr::null(),
+                std::ptr::null(),
+                data.len() as u32,
+                data.as_ptr(),
+            )
+        };
+
+        if status != 0 {
+            Err(io::Error::from_raw_os_error(status as i32))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Appender for EtwAppender {
+    fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+
+    fn write(&self, value: &str) -> io::Result<()> {
+        self.emit(&Level::INFO, "event", value, None)
+    }
+
+    fn write_record(&self, record: &Record<'_>, value: &str) -> io::Result<()> {
+        self.emit(
+            record.level(),
+            record.metadata().name(),
+            value,
+            Some(record),
+        )
+    }
+}
+
+impl Drop for EtwAppender {
+    fn drop(&mut self) {
+        unsafe {
+            EventUnregister(self.handle);
+        }
+    }
+}
+
+fn etw_level(level: &Level) -> u8 {
+    match *level {
+        Level::ERROR => 2,
+        Level::WARN => 3,
+        Level::INFO => 4,
+        Level::DEBUG | Level::TRACE => 5,
+    }
+}
+
+/// Writes a TraceLogging metadata block: little-endian `u16` total size followed by the contents.
+fn push_sized<F: FnOnce(&mut Vec<u8>)>(buf: &mut Vec<u8>, f: F) {
+    let start = buf.len();
+    buf.extend_from_slice(&[0, 0]);
+    f(buf);
+
+    let size = (buf.len() - start) as u16;
+    buf[start..start + 2].copy_from_slice(&size.to_le_bytes());
+}
+
+fn utf16(v: &str) -> Vec<u8> {
+    v.encode_utf16()
+        .chain(std::iter::once(0))
+        .flat_map(|i| i.to_le_bytes())
+        .collect()
+}
+
+fn descriptor(data: &[u8], ty: u8) -> EVENT_DATA_DESCRIPTOR {
+    EVENT_DATA_DESCRIPTOR {
+        Ptr: data.as_ptr() as u64,
+        Size: data.len() as u32,
+        Anonymous: EVENT_DATA_DESCRIPTOR_0 {
+            Anonymous: EVENT_DATA_DESCRIPTOR_0_0 {
+                Type: ty,
+                Reserved1: 0,
+                Reserved2: 0,
+            },
+        },
+    }
+}
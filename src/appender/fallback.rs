@@ -0,0 +1,222 @@
+use crate::appender::{Appender, Record};
+use crate::diagnostics::{self, Diagnostic};
+use crate::pattern::Pattern;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+struct State {
+    failures: u32,
+    // set while lines are diverted to stderr
+    retry_at: Option<Instant>,
+    backoff: Duration,
+}
+
+/// Diverts lines to stderr once the inner appender failed `max_failures` times in a row.
+///
+/// While diverted, the inner appender is retried with an exponential backoff; the first
+/// successful write ends the diversion. Both switches are reported as
+/// [diagnostics](crate::diagnostics), so a full disk or a dead socket doesn't leave the
+/// application silently unlogged.
+pub struct FallbackAppender<A: Appender> {
+    inner: A,
+    max_failures: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    state: Mutex<State>,
+}
+
+impl<A: Appender> FallbackAppender<A> {
+    pub fn new(inner: A, max_failures: u32) -> Self {
+        Self {
+            inner,
+            max_failures: max_failures.max(1),
+            initial_backoff: INITIAL_BACKOFF,
+            max_backoff: MAX_BACKOFF,
+            state: Mutex::new(State {
+                failures: 0,
+                retry_at: None,
+                backoff: INITIAL_BACKOFF,
+            }),
+        }
+    }
+
+    /// Retries the inner appender after `initial`, doubling the delay up to `max`.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self.state.get_mut().unwrap().backoff = initial;
+        self
+    }
+
+    /// Whether lines currently go to stderr instead of the inner appender.
+    pub fn is_diverted(&self) -> bool {
+        self.state.lock().unwrap().retry_at.is_some()
+    }
+
+    fn write_with<F: Fn(&A) -> io::Result<()>>(&self, values: &[&str], write: F) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(retry_at) = state.retry_at {
+            if Instant::now() < retry_at {
+                return write_stderr(values);
+            }
+        }
+
+        match write(&self.inner) {
+            Ok(()) => {
+                let recovered = state.retry_at.take().is_some();
+                state.failures = 0;
+                state.backoff = self.initial_backoff;
+                drop(state);
+
+                // the handler may log, which can end up in this appender again
+                if recovered {
+                    diagnostics::report(Diagnostic::AppenderRecovered);
+                }
+                Ok(())
+            }
+            Err(e) => {
+                state.failures = state.failures.saturating_add(1);
+                if state.failures < self.max_failures {
+                    return Err(e);
+                }
+
+                let failover = state.retry_at.is_none();
+                let failures = state.failures;
+                state.retry_at = Some(Instant::now() + state.backoff);
+                state.backoff = (state.backoff * 2).min(self.max_backoff);
+                drop(state);
+
+                if failover {
+                    diagnostics::report(Diagnostic::AppenderFailover {
+                        failures,
+                        error: &e,
+                    });
+                }
+                write_stderr(values)
+            }
+        }
+    }
+}
+
+impl<A: Appender> Appender for FallbackAppender<A> {
+    fn pattern(&self) -> &Pattern {
+        self.inner.pattern()
+    }
+
+    fn pattern_for(&self, target: &str) -> &Pattern {
+        self.inner.pattern_for(target)
+    }
+
+    fn write(&self, value: &str) -> io::Result<()> {
+        self.write_with(&[value], |inner| inner.write(value))
+    }
+
+    fn write_record(&self, record: &Record<'_>, value: &str) -> io::Result<()> {
+        self.write_with(&[value], |inner| inner.write_record(record, value))
+    }
+
+    fn write_batch(&self, lines: &[(Option<&Record<'_>>, &str)]) -> io::Result<()> {
+        let values: Vec<&str> = lines.iter().map(|(_, i)| *i).collect();
+        self.write_with(&values, |inner| inner.write_batch(lines))
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        if self.is_diverted() {
+            return io::stderr().flush();
+        }
+
+        self.inner.flush()
+    }
+
+    fn close(&self, deadline: Instant) -> io::Result<()> {
+        // the inner appender is released even while diverted
+        io::stderr().flush()?;
+        self.inner.close(deadline)
+    }
+}
+
+fn write_stderr(values: &[&str]) -> io::Result<()> {
+    let mut stderr = io::stderr().lock();
+    for value in values {
+        writeln!(stderr, "{}", value)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::appender::fallback::FallbackAppender;
+    use crate::appender::Appender;
+    use crate::diagnostics::{self, Diagnostic};
+    use crate::pattern::Pattern;
+    use std::io;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    struct FlakyAppender {
+        pattern: Pattern,
+        failing: AtomicBool,
+    }
+
+    impl Appender for FlakyAppender {
+        fn pattern(&self) -> &Pattern {
+            &self.pattern
+        }
+
+        fn write(&self, _: &str) -> io::Result<()> {
+            if self.failing.load(Ordering::Relaxed) {
+                Err(io::ErrorKind::StorageFull.into())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn diverts_after_consecutive_failures() {
+        let notices = Arc::new(Mutex::new(Vec::new()));
+        diagnostics::set_handler({
+            let notices = notices.clone();
+            move |diagnostic| {
+                if matches!(
+                    diagnostic,
+                    Diagnostic::AppenderFailover { .. } | Diagnostic::AppenderRecovered
+                ) {
+                    notices.lock().unwrap().push(diagnostic.to_string());
+                }
+            }
+        });
+
+        let appender = FallbackAppender::new(
+            FlakyAppender {
+                pattern: Pattern::new(vec![]),
+                failing: AtomicBool::new(true),
+            },
+            2,
+        )
+        .with_backoff(Duration::ZERO, Duration::ZERO);
+
+        assert!(appender.write("first").is_err());
+        assert!(!appender.is_diverted());
+
+        assert!(appender.write("second").is_ok());
+        assert!(appender.is_diverted());
+
+        appender.inner.failing.store(false, Ordering::Relaxed);
+        assert!(appender.write("third").is_ok());
+        assert!(!appender.is_diverted());
+        diagnostics::reset_handler();
+
+        let notices = notices.lock().unwrap();
+        assert_eq!(notices.len(), 2);
+        assert!(notices[0].starts_with("appender failed 2 times in a row"));
+        assert_eq!(notices[1], "appender recovered, resuming");
+    }
+}
@@ -0,0 +1,667 @@
+use crate::appender::writer::WriterAppender;
+use crate::appender::{Appender, Record};
+use crate::clock::{self, Clock};
+use crate::diagnostics::{self, Diagnostic};
+use crate::json::{self, JsonFormat};
+use crate::pattern::Pattern;
+use chrono::{DateTime, Local};
+use std::fmt::{Debug, Formatter};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, IoSlice, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Interval of time based rotation, see [`FileOptions::rotate_every`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RotationPeriod {
+    Hourly,
+    Daily,
+}
+
+impl RotationPeriod {
+    /// Identifies the period `time` falls into.
+    fn key(&self, time: DateTime<Local>) -> String {
+        match self {
+            RotationPeriod::Hourly => time.format("%Y%m%d%H").to_string(),
+            RotationPeriod::Daily => time.format("%Y%m%d").to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Rotation {
+    max_size: Option<u64>,
+    period: Option<RotationPeriod>,
+}
+
+/// Options shared by the file appenders.
+#[derive(Default, Clone)]
+pub struct FileOptions {
+    rotate_on_startup: Option<bool>,
+    lazy: bool,
+    header: Option<Arc<Pattern>>,
+    footer: Option<Arc<Pattern>>,
+    rotation: Rotation,
+    clock: Option<Arc<dyn Clock>>,
+    // headers and footers are written as JSON objects
+    json: bool,
+    #[cfg(feature = "checksum")]
+    checksum: bool,
+}
+
+impl Debug for FileOptions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileOptions")
+            .field("rotate_on_startup", &self.rotate_on_startup)
+            .field("lazy", &self.lazy)
+            .field("header", &self.header)
+            .field("footer", &self.footer)
+            .field("rotation", &self.rotation)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FileOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves an existing file aside when the appender is created, so each run starts with
+    /// a fresh file. With `only_if_non_empty` empty files are reused.
+    ///
+    /// The old file keeps its name with the time of the move appended, e.g.
+    /// `app.log.20240131-142501`.
+    pub fn rotate_on_startup(mut self, only_if_non_empty: bool) -> Self {
+        self.rotate_on_startup = Some(only_if_non_empty);
+        self
+    }
+
+    /// Doesn't create or open the file before the first line is written, so short-lived
+    /// tools and rarely used routes don't leave empty files behind.
+    pub fn lazy(mut self, lazy: bool) -> Self {
+        self.lazy = lazy;
+        self
+    }
+
+    /// Writes `header` as a line whenever the file is opened, e.g. a start banner or a
+    /// schema version. See [`Pattern::render_standalone`] for the placeholders available.
+    pub fn header(mut self, header: Pattern) -> Self {
+        self.header = Some(Arc::new(header));
+        self
+    }
+
+    /// Writes `footer` as a line when the appender closes the file.
+    pub fn footer(mut self, footer: Pattern) -> Self {
+        self.footer = Some(Arc::new(footer));
+        self
+    }
+
+    /// Moves the file aside and starts a new one before a line would make it grow past
+    /// `max_size` bytes. Rolled files are named like with
+    /// [`rotate_on_startup`](Self::rotate_on_startup).
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.rotation.max_size = Some(max_size.max(1));
+        self
+    }
+
+    /// Moves the file aside and starts a new one with the first line of every `period`.
+    pub fn rotate_every(mut self, period: RotationPeriod) -> Self {
+        self.rotation.period = Some(period);
+        self
+    }
+
+    /// Time source for time based rotation, the names of rolled files and the placeholders
+    /// of headers and footers, give it the clock passed to
+    /// [`ConfigurableLayer::with_clock`](crate::ConfigurableLayer::with_clock).
+    /// Defaults to [`clock::system`].
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Writes the SHA-256 of every rolled file next to it, as `<name>.sha256` in the format
+    /// of `sha256sum`, so archived logs can be checked with [`verify_checksum`] or
+    /// `sha256sum -c` later.
+    #[cfg(feature = "checksum")]
+    pub fn checksum_on_rotation(mut self, checksum: bool) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// The options without size or time based rotation, for appenders managing the size
+    /// of their file themselves.
+    pub(crate) fn without_rotation(&self) -> Self {
+        Self {
+            rotation: Rotation::default(),
+            ..self.clone()
+        }
+    }
+
+    /// The options writing headers and footers as JSON objects, for appenders writing
+    /// newline-delimited JSON.
+    pub(crate) fn json(&self) -> Self {
+        Self {
+            json: true,
+            ..self.clone()
+        }
+    }
+
+    pub(crate) fn clock_or_system(&self) -> Arc<dyn Clock> {
+        self.clock.clone().unwrap_or_else(clock::system)
+    }
+
+    /// Applies the options to `path` and opens it, unless opening is deferred.
+    pub(crate) fn open(&self, path: &Path, open_options: OpenOptions) -> io::Result<LogFile> {
+        let clock = self.clock_or_system();
+        if let Some(only_if_non_empty) = self.rotate_on_startup {
+            let rolled = roll(path, only_if_non_empty, clock.now())?;
+            #[cfg(feature = "checksum")]
+            if let Some(rolled) = rolled.filter(|_| self.checksum) {
+                checksum::write(&rolled)?;
+            }
+            #[cfg(not(feature = "checksum"))]
+            let _ = rolled;
+        }
+
+        let mut file = LogFile {
+            path: path.to_path_buf(),
+            open_options,
+            file: None,
+            header: self.header.clone(),
+            footer: self.footer.clone(),
+            rotation: self.rotation,
+            clock,
+            json: self.json,
+            size: 0,
+            period: None,
+            at_line_start: true,
+            #[cfg(feature = "checksum")]
+            checksum: self.checksum,
+        };
+
+        if !self.lazy {
+            file.get()?;
+        }
+
+        Ok(file)
+    }
+}
+
+/// A file appenders write to, opened on first use and positioned at its end.
+pub(crate) struct LogFile {
+    path: PathBuf,
+    open_options: OpenOptions,
+    file: Option<File>,
+    header: Option<Arc<Pattern>>,
+    footer: Option<Arc<Pattern>>,
+    rotation: Rotation,
+    clock: Arc<dyn Clock>,
+    json: bool,
+    // bytes in the open file
+    size: u64,
+    // rotation period the open file belongs to
+    period: Option<String>,
+    // rotation waits for the end of the line being written
+    at_line_start: bool,
+    #[cfg(feature = "checksum")]
+    checksum: bool,
+}
+
+impl LogFile {
+    pub(crate) fn get(&mut self) -> io::Result<&mut File> {
+        if self.file.is_none() {
+            let mut file = self.open_options.open(&self.path)?;
+            self.size = file.seek(SeekFrom::End(0))?;
+            self.period = self.rotation.period.map(|i| i.key(self.clock.now()));
+            self.at_line_start = true;
+
+            if let Some(header) = &self.header {
+                let header = format!("{}\n", self.banner(header));
+                file.write_all(header.as_bytes())?;
+                self.size += header.len() as u64;
+            }
+
+            self.file = Some(file);
+        }
+
+        Ok(self.file.as_mut().unwrap())
+    }
+
+    /// Opens the file, first rotating it if writing `incoming` more bytes is due to.
+    fn get_for_write(&mut self, incoming: usize) -> io::Result<&mut File> {
+        self.get()?;
+
+        if self.at_line_start && self.rotation_due(incoming) {
+            self.close()?;
+            let rolled = roll(&self.path, false, self.clock.now())?;
+            #[cfg(feature = "checksum")]
+            if let Some(rolled) = rolled.filter(|_| self.checksum) {
+                checksum::write(&rolled)?;
+            }
+            #[cfg(not(feature = "checksum"))]
+            let _ = rolled;
+        }
+
+        self.get()
+    }
+
+    fn rotation_due(&self, incoming: usize) -> bool {
+        let too_large = self
+            .rotation
+            .max_size
+            .is_some_and(|max| self.size > 0 && self.size + incoming as u64 > max);
+        let period_ended = self
+            .rotation
+            .period
+            .is_some_and(|i| self.period.as_deref() != Some(&i.key(self.clock.now())));
+
+        too_large || period_ended
+    }
+
+    /// Keeps track of what was written for rotation.
+    fn wrote(&mut self, bufs: &[IoSlice<'_>], mut written: usize) {
+        self.size += written as u64;
+
+        for buf in bufs {
+            if written <= buf.len() {
+                if written > 0 {
+                    self.at_line_start = buf[written - 1] == b'\n';
+                }
+                break;
+            }
+
+            written -= buf.len();
+        }
+    }
+
+    /// Writes the footer and closes the file; it's opened again by the next write.
+    pub(crate) fn close(&mut self) -> io::Result<()> {
+        let Some(mut file) = self.file.take() else {
+            return Ok(());
+        };
+
+        if let Some(footer) = &self.footer {
+            writeln!(file, "{}", self.banner(footer))?;
+        }
+
+        file.flush()
+    }
+
+    /// Renders a header or footer, as an object holding it as the message in JSON files.
+    fn banner(&self, pattern: &Pattern) -> String {
+        let line = pattern.render_standalone(&*self.clock);
+        if !self.json {
+            return line;
+        }
+
+        let mut object = String::with_capacity(line.len() + 64);
+        json::write_message(&mut object, &line, &self.clock.now(), JsonFormat::compact());
+        object
+    }
+
+    pub(crate) fn is_open(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// Current size of the file on disk, zero if it doesn't exist yet.
+    pub(crate) fn len(&self) -> io::Result<u64> {
+        match fs::metadata(&self.path) {
+            Ok(metadata) => Ok(metadata.len()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub(crate) fn set_len(&mut self, len: u64) -> io::Result<()> {
+        self.get()?.set_len(len)
+    }
+}
+
+impl Drop for LogFile {
+    fn drop(&mut self) {
+        if let Err(error) = self.close() {
+            diagnostics::report(Diagnostic::AppenderError {
+                target: None,
+                error: &error,
+            });
+        }
+    }
+}
+
+impl Write for LogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_vectored(&[IoSlice::new(buf)])
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let incoming = bufs.iter().map(|i| i.len()).sum();
+        let written = self.get_for_write(incoming)?.write_vectored(bufs)?;
+        self.wrote(bufs, written);
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Read for LogFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.get()?.read(buf)
+    }
+}
+
+impl Seek for LogFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.get()?.seek(pos)
+    }
+}
+
+#[cfg(feature = "checksum")]
+pub use checksum::verify as verify_checksum;
+
+#[cfg(feature = "checksum")]
+mod checksum {
+    use sha2::{Digest, Sha256};
+    use std::fmt::Write as _;
+    use std::fs::{self, File};
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    fn sidecar(path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".sha256");
+        PathBuf::from(name)
+    }
+
+    fn digest(path: &Path) -> io::Result<String> {
+        let mut hasher = Sha256::new();
+        io::copy(&mut File::open(path)?, &mut hasher)?;
+
+        Ok(hasher
+            .finalize()
+            .iter()
+            .fold(String::with_capacity(64), |mut acc, i| {
+                let _ = write!(acc, "{:02x}", i);
+                acc
+            }))
+    }
+
+    /// Writes the checksum of `path` to its sidecar file.
+    pub(super) fn write(path: &Path) -> io::Result<()> {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        fs::write(sidecar(path), format!("{}  {}\n", digest(path)?, name))
+    }
+
+    /// Checks a rolled file against the checksum written next to it, see
+    /// [`FileOptions::checksum_on_rotation`](super::FileOptions::checksum_on_rotation).
+    pub fn verify<P: AsRef<Path>>(path: P) -> io::Result<bool> {
+        let path = path.as_ref();
+        let expected = fs::read_to_string(sidecar(path))?;
+        let expected = expected.split_whitespace().next().unwrap_or_default();
+
+        Ok(digest(path)? == expected)
+    }
+}
+
+/// Appends lines rendered with `pattern` to a file. Rotation, lazy opening, headers and
+/// footers are set up through [`FileOptions`].
+pub struct FileAppender {
+    writer: WriterAppender<LogFile>,
+}
+
+impl FileAppender {
+    pub fn new<P: AsRef<Path>>(pattern: Pattern, path: P) -> io::Result<Self> {
+        Self::with_options(pattern, path, FileOptions::default())
+    }
+
+    pub fn with_options<P: AsRef<Path>>(
+        pattern: Pattern,
+        path: P,
+        options: FileOptions,
+    ) -> io::Result<Self> {
+        let mut open_options = OpenOptions::new();
+        open_options.create(true).append(true);
+        let file = options.open(path.as_ref(), open_options)?;
+
+        Ok(Self {
+            writer: WriterAppender::new(pattern, file),
+        })
+    }
+}
+
+impl Appender for FileAppender {
+    fn pattern(&self) -> &Pattern {
+        self.writer.pattern()
+    }
+
+    fn write(&self, value: &str) -> io::Result<()> {
+        self.writer.write(value)
+    }
+
+    fn write_batch(&self, lines: &[(Option<&Record<'_>>, &str)]) -> io::Result<()> {
+        self.writer.write_batch(lines)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    fn close(&self, _: Instant) -> io::Result<()> {
+        self.writer.writer().close()
+    }
+}
+
+/// Renames `path` to a name with `now` appended, returning the new name.
+fn roll(path: &Path, only_if_non_empty: bool, now: DateTime<Local>) -> io::Result<Option<PathBuf>> {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    if only_if_non_empty && metadata.len() == 0 {
+        return Ok(None);
+    }
+
+    let mut name = path.as_os_str().to_owned();
+    name.push(now.format(".%Y%m%d-%H%M%S").to_string());
+
+    // several runs within a second get a counter
+    let mut target = PathBuf::from(&name);
+    let mut counter = 1;
+    while target.exists() {
+        let mut numbered = name.clone();
+        numbered.push(format!(".{}", counter));
+        target = PathBuf::from(numbered);
+        counter += 1;
+    }
+
+    fs::rename(path, &target)?;
+    Ok(Some(target))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::appender::file::{FileAppender, FileOptions};
+    use crate::appender::Appender;
+    use crate::clock::TestClock;
+    use crate::pattern::Pattern;
+    use chrono::{Local, TimeZone};
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+
+    #[test]
+    fn rotates_existing_file() {
+        let dir = std::env::temp_dir().join(format!(
+            "tracing-configurable-rotate-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("app.log");
+        let options = FileOptions::new().rotate_on_startup(true).lazy(true);
+
+        fs::write(&path, "").unwrap();
+        options.open(&path, OpenOptions::new()).unwrap();
+        assert!(path.exists());
+
+        fs::write(&path, "previous run\n").unwrap();
+        options.open(&path, OpenOptions::new()).unwrap();
+        options.open(&path, OpenOptions::new()).unwrap();
+        assert!(!path.exists());
+
+        let rolled: Vec<_> = fs::read_dir(&dir).unwrap().collect();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(rolled.len(), 1);
+    }
+
+    #[test]
+    fn lazy_file_is_created_on_first_write() {
+        let path = std::env::temp_dir().join(format!(
+            "tracing-configurable-lazy-{}.log",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+
+        let mut open_options = OpenOptions::new();
+        open_options.create(true).append(true);
+        let mut file = FileOptions::new()
+            .lazy(true)
+            .open(&path, open_options)
+            .unwrap();
+        assert!(!path.exists());
+
+        file.write_all(b"first\n").unwrap();
+        assert!(path.exists());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "parse")]
+    fn writes_header_and_footer() {
+        let path = std::env::temp_dir().join(format!(
+            "tracing-configurable-header-{}.log",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+
+        let mut open_options = OpenOptions::new();
+        open_options.create(true).append(true);
+        let clock = TestClock::new(Local.with_ymd_and_hms(2024, 3, 1, 8, 30, 0).unwrap());
+        let mut file = FileOptions::new()
+            .header(Pattern::try_parse("# started $datetime(fmt = '%H:%M') v1").unwrap())
+            .footer(Pattern::try_parse("# closed $datetime(fmt = '%H:%M')").unwrap())
+            .clock(clock.clone())
+            .open(&path, open_options)
+            .unwrap();
+
+        file.write_all(b"line\n").unwrap();
+        clock.advance(std::time::Duration::from_secs(60));
+        drop(file);
+
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(content, "# started 08:30 v1\nline\n# closed 08:31\n");
+    }
+
+    #[test]
+    fn rotates_by_size_at_line_boundaries() {
+        let dir =
+            std::env::temp_dir().join(format!("tracing-configurable-size-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("app.log");
+        let mut open_options = OpenOptions::new();
+        open_options.create(true).append(true);
+        let mut file = FileOptions::new()
+            .max_size(20)
+            .open(&path, open_options)
+            .unwrap();
+
+        file.write_all(b"first line\n").unwrap();
+        // a line written in pieces is never split across files, even if it ends up too long
+        file.write_all(b"second").unwrap();
+        file.write_all(b" line\n").unwrap();
+        file.write_all(b"third line\n").unwrap();
+        drop(file);
+
+        let mut contents: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|i| fs::read_to_string(i.unwrap().path()).unwrap())
+            .collect();
+        contents.sort();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(contents, ["first line\nsecond line\n", "third line\n"]);
+    }
+
+    #[test]
+    fn appender_names_rolled_files_by_clock() {
+        let dir = std::env::temp_dir().join(format!(
+            "tracing-configurable-appender-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("app.log");
+        fs::write(&path, "previous run\n").unwrap();
+
+        let clock = TestClock::new(Local.with_ymd_and_hms(2024, 3, 1, 8, 30, 0).unwrap());
+        let options = FileOptions::new()
+            .rotate_on_startup(true)
+            .lazy(true)
+            .clock(clock);
+        let appender = FileAppender::with_options(Pattern::new(vec![]), &path, options).unwrap();
+        assert!(!path.exists());
+
+        appender.write("current run").unwrap();
+        drop(appender);
+
+        let current = fs::read_to_string(&path).unwrap();
+        let previous = fs::read_to_string(dir.join("app.log.20240301-083000")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(current, "current run\n");
+        assert_eq!(previous, "previous run\n");
+    }
+
+    #[test]
+    #[cfg(feature = "checksum")]
+    fn writes_checksum_of_rolled_files() {
+        use crate::appender::file::verify_checksum;
+
+        let dir = std::env::temp_dir().join(format!(
+            "tracing-configurable-checksum-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let clock = TestClock::new(Local.with_ymd_and_hms(2024, 3, 1, 8, 30, 0).unwrap());
+        let options = FileOptions::new()
+            .max_size(16)
+            .checksum_on_rotation(true)
+            .clock(clock);
+        let appender =
+            FileAppender::with_options(Pattern::new(vec![]), dir.join("app.log"), options).unwrap();
+        appender.write("first line").unwrap();
+        appender.write("second line").unwrap();
+        drop(appender);
+
+        let rolled = dir.join("app.log.20240301-083000");
+        let sidecar = fs::read_to_string(dir.join("app.log.20240301-083000.sha256")).unwrap();
+        assert!(sidecar.ends_with("  app.log.20240301-083000\n"));
+        assert!(verify_checksum(&rolled).unwrap());
+
+        fs::write(&rolled, "first lime\n").unwrap();
+        assert!(!verify_checksum(&rolled).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
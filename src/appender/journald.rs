@@ -0,0 +1,286 @@
+use crate::appender::{Appender, Record};
+use crate::config::matcher::TargetMatcher;
+use crate::pattern::Pattern;
+use anyhow::anyhow;
+use std::io;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use tracing::Level;
+
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+
+const LOG_ERR: u8 = 3;
+const LOG_WARNING: u8 = 4;
+const LOG_INFO: u8 = 6;
+const LOG_DEBUG: u8 = 7;
+
+/// Priorities by level, most severe first.
+const DEFAULT_PRIORITIES: [u8; 5] = [LOG_ERR, LOG_WARNING, LOG_INFO, LOG_DEBUG, LOG_DEBUG];
+
+fn level_index(level: &Level) -> usize {
+    match *level {
+        Level::ERROR => 0,
+        Level::WARN => 1,
+        Level::INFO => 2,
+        Level::DEBUG => 3,
+        Level::TRACE => 4,
+    }
+}
+
+/// Which journald `PRIORITY` and `MESSAGE_ID` an event gets, by its target and level.
+///
+/// Rules apply to a target and the modules below it, the most specific one wins (see
+/// [`TargetMatcher`]). A priority rule starts from the default mapping (ERROR is `err`,
+/// WARN `warning`, INFO `info`, DEBUG and TRACE `debug`), not from the rules of its parents.
+#[derive(Default)]
+pub struct JournaldMapping {
+    priorities: TargetMatcher<[u8; 5]>,
+    message_ids: TargetMatcher<String>,
+}
+
+impl JournaldMapping {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Logs `level` events of `target` with `priority`, from 0 (`emerg`) to 7 (`debug`).
+    pub fn priority<S: AsRef<str>>(mut self, target: S, level: Level, priority: u8) -> Self {
+        let priorities = self
+            .priorities
+            .get_or_insert_with(target, || DEFAULT_PRIORITIES);
+        priorities[level_index(&level)] = priority.min(LOG_DEBUG);
+        self
+    }
+
+    /// Tags events of `target` with `MESSAGE_ID=id`, so journal consumers can select them
+    /// with `journalctl MESSAGE_ID=...`. `id` is a 128-bit id as 32 hex digits, e.g. from
+    /// `systemd-id128 new`.
+    pub fn message_id<S: AsRef<str>>(mut self, target: S, id: &str) -> Result<Self, anyhow::Error> {
+        let id = id.replace('-', "").to_ascii_lowercase();
+        if id.len() != 32 || !id.chars().all(|i| i.is_ascii_hexdigit()) {
+            return Err(anyhow!("message id `{}` isn't 32 hex digits", id));
+        }
+
+        self.message_ids.insert(target, id);
+        Ok(self)
+    }
+
+    fn priority_of(&self, level: &Level, target: &str) -> u8 {
+        self.priorities.get(target).unwrap_or(&DEFAULT_PRIORITIES)[level_index(level)]
+    }
+}
+
+/// Sends events to the systemd journal over its native protocol.
+///
+/// Besides `MESSAGE` (the rendered line) and `PRIORITY`, entries carry the target as
+/// `TARGET`, the source location as `CODE_FILE`/`CODE_LINE` and the event fields with
+/// their names upper-cased, e.g. `order_id` as `ORDER_ID`. Entries have to fit into a single
+/// datagram.
+pub struct JournaldAppender {
+    pattern: Pattern,
+    socket: UnixDatagram,
+    path: PathBuf,
+    identifier: String,
+    mapping: JournaldMapping,
+}
+
+impl JournaldAppender {
+    pub fn new(pattern: Pattern) -> io::Result<Self> {
+        Self::with_socket(pattern, JOURNALD_SOCKET)
+    }
+
+    /// Sends to the socket at `path` instead of journald's default one.
+    pub fn with_socket<P: AsRef<Path>>(pattern: Pattern, path: P) -> io::Result<Self> {
+        let identifier = std::env::args()
+            .next()
+            .as_deref()
+            .map(Path::new)
+            .and_then(Path::file_name)
+            .map(|i| i.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "tracing".to_string());
+
+        Ok(Self {
+            pattern,
+            socket: UnixDatagram::unbound()?,
+            path: path.as_ref().to_path_buf(),
+            identifier,
+            mapping: JournaldMapping::default(),
+        })
+    }
+
+    /// `SYSLOG_IDENTIFIER` of the entries, the program name by default.
+    pub fn with_identifier<S: Into<String>>(mut self, identifier: S) -> Self {
+        self.identifier = identifier.into();
+        self
+    }
+
+    pub fn with_mapping(mut self, mapping: JournaldMapping) -> Self {
+        self.mapping = mapping;
+        self
+    }
+
+    fn entry(&self, priority: u8, value: &str) -> Vec<u8> {
+        let mut entry = Vec::with_capacity(value.len() + 128);
+        push_field(&mut entry, "MESSAGE", value);
+        push_field(&mut entry, "PRIORITY", &priority.to_string());
+        push_field(&mut entry, "SYSLOG_IDENTIFIER", &self.identifier);
+        entry
+    }
+}
+
+impl Appender for JournaldAppender {
+    fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+
+    fn write(&self, value: &str) -> io::Result<()> {
+        let entry = self.entry(LOG_INFO, value);
+        self.socket.send_to(&entry, &self.path).map(|_| ())
+    }
+
+    fn write_record(&self, record: &Record<'_>, value: &str) -> io::Result<()> {
+        let target = record.target();
+        let mut entry = self.entry(self.mapping.priority_of(record.level(), target), value);
+
+        push_field(&mut entry, "TARGET", target);
+        if let Some(id) = self.mapping.message_ids.get(target) {
+            push_field(&mut entry, "MESSAGE_ID", id);
+        }
+        if let Some(file) = record.metadata().file() {
+            push_field(&mut entry, "CODE_FILE", file);
+        }
+        if let Some(line) = record.metadata().line() {
+            push_field(&mut entry, "CODE_LINE", &line.to_string());
+        }
+
+        for (name, values) in record.fields().values() {
+            let Some(name) = field_name(name) else {
+                continue;
+            };
+
+            for value in values {
+                push_field(&mut entry, &name, &value.to_string());
+            }
+        }
+
+        self.socket.send_to(&entry, &self.path).map(|_| ())
+    }
+}
+
+/// Journald field name for an event field: upper-cased, other characters than letters,
+/// digits and `_` replaced. Names that can't be made valid, or that would clash with the
+/// fields written by the appender, are left out.
+fn field_name(name: &str) -> Option<String> {
+    let name: String = name
+        .chars()
+        .map(|i| match i {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => i.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .take(64)
+        .collect();
+
+    let reserved = matches!(
+        name.as_str(),
+        "MESSAGE"
+            | "MESSAGE_ID"
+            | "PRIORITY"
+            | "SYSLOG_IDENTIFIER"
+            | "TARGET"
+            | "CODE_FILE"
+            | "CODE_LINE"
+    );
+
+    Some(name).filter(|i| i.starts_with(|c: char| c.is_ascii_uppercase()) && !reserved)
+}
+
+/// Appends a field, values with line breaks in the length-prefixed binary form.
+fn push_field(entry: &mut Vec<u8>, name: &str, value: &str) {
+    entry.extend_from_slice(name.as_bytes());
+
+    if value.contains('\n') {
+        entry.push(b'\n');
+        entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        entry.push(b'=');
+    }
+
+    entry.extend_from_slice(value.as_bytes());
+    entry.push(b'\n');
+}
+
+#[cfg(test)]
+mod test {
+    use crate::appender::journald::{field_name, JournaldAppender, JournaldMapping};
+    use crate::pattern::{Pattern, PatternItem, Placeholder, PlaceholderType};
+    use crate::testing::TestConfig;
+    use crate::ConfigurableLayer;
+    use std::collections::HashMap;
+    use std::os::unix::net::UnixDatagram;
+    use tracing::{error, info, Level};
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::registry;
+
+    #[test]
+    fn sends_mapped_entries() {
+        let path = std::env::temp_dir().join(format!(
+            "tracing-configurable-journald-{}.sock",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let journal = UnixDatagram::bind(&path).unwrap();
+
+        let mapping = JournaldMapping::new()
+            .priority("my_app::payments", Level::INFO, 5)
+            .message_id("my_app::payments", "fc2e22bc6ee647b6b90729ab34a250b1")
+            .unwrap();
+        let appender = JournaldAppender::with_socket(
+            Pattern::new(vec![PatternItem::Placeholder(Placeholder::new(
+                PlaceholderType::Message,
+                HashMap::new(),
+                vec![],
+            ))]),
+            &path,
+        )
+        .unwrap()
+        .with_identifier("my_app")
+        .with_mapping(mapping);
+
+        let layer = ConfigurableLayer::new(TestConfig::new(appender));
+        tracing::subscriber::with_default(registry().with(layer), || {
+            info!(target: "my_app::payments::card", order_id = 7, "charged\nok");
+            error!(target: "my_app::db", "failed");
+        });
+
+        let mut buf = vec![0; 4096];
+        let len = journal.recv(&mut buf).unwrap();
+        let first = buf[..len].to_vec();
+        let len = journal.recv(&mut buf).unwrap();
+        let second = String::from_utf8_lossy(&buf[..len]).into_owned();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut message = b"MESSAGE\n".to_vec();
+        message.extend_from_slice(&10u64.to_le_bytes());
+        message.extend_from_slice(b"charged\nok\n");
+        assert!(first.starts_with(&message));
+
+        let first = String::from_utf8_lossy(&first).into_owned();
+        assert!(first.contains("\nPRIORITY=5\nSYSLOG_IDENTIFIER=my_app\n"));
+        assert!(first.contains("\nMESSAGE_ID=fc2e22bc6ee647b6b90729ab34a250b1\n"));
+        assert!(first.contains("\nORDER_ID=7\n"));
+
+        assert!(second.starts_with("MESSAGE=failed\nPRIORITY=3\n"));
+        assert!(!second.contains("MESSAGE_ID"));
+    }
+
+    #[test]
+    fn maps_field_names() {
+        assert_eq!(field_name("order_id").as_deref(), Some("ORDER_ID"));
+        assert_eq!(field_name("http.status").as_deref(), Some("HTTP_STATUS"));
+        assert_eq!(field_name("_private"), None);
+        assert_eq!(field_name("priority"), None);
+        assert!(JournaldMapping::new()
+            .message_id("my_app", "not-an-id")
+            .is_err());
+    }
+}
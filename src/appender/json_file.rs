@@ -0,0 +1,422 @@
+use crate::appender::file::{FileOptions, LogFile};
+use crate::appender::writer::WriterAppender;
+use crate::appender::{Appender, Record};
+use crate::clock::{self, Clock};
+use crate::json;
+use crate::pattern::{Pattern, PatternItem, Placeholder, PlaceholderType};
+use chrono::{DateTime, Local};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
+pub use crate::json::JsonFormat;
+
+/// Writes events as JSON objects to any [`Write`] implementation, e.g. pretty and colored
+/// to stdout during development. See [`JsonFileAppender`] for files.
+///
+/// Each object holds the timestamp, level, target, message and fields of one event; the
+/// pattern only matters for lines written without a record, which become the message.
+pub struct JsonAppender<W: Write + Send> {
+    writer: WriterAppender<W>,
+    format: JsonFormat,
+    clock: Arc<dyn Clock>,
+}
+
+impl<W: Write + Send> JsonAppender<W> {
+    pub fn new(writer: W) -> Self {
+        let pattern = Pattern::new(vec![PatternItem::Placeholder(Placeholder::new(
+            PlaceholderType::Message,
+            HashMap::new(),
+            vec![],
+        ))]);
+
+        Self {
+            writer: WriterAppender::new(pattern, writer),
+            format: JsonFormat::default(),
+            clock: clock::system(),
+        }
+    }
+
+    pub fn with_format(mut self, format: JsonFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Renders an event, or a line that came without one, as a JSON object.
+    fn object(&self, record: Option<&Record<'_>>, value: &str, now: &DateTime<Local>) -> String {
+        let mut object = String::with_capacity(value.len() + 256);
+        match record {
+            Some(record) => json::write_record(&mut object, record, now, self.format),
+            None => json::write_message(&mut object, value, now, self.format),
+        }
+
+        object
+    }
+
+    /// Clock timestamping lines written without an event, events carry their own time.
+    /// Defaults to [`clock::system`].
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+}
+
+impl<W: Write + Send> Appender for JsonAppender<W> {
+    fn pattern(&self) -> &Pattern {
+        self.writer.pattern()
+    }
+
+    fn write(&self, value: &str) -> io::Result<()> {
+        self.writer
+            .write(&self.object(None, value, &self.clock.now()))
+    }
+
+    fn write_record(&self, record: &Record<'_>, value: &str) -> io::Result<()> {
+        self.writer
+            .write(&self.object(Some(record), value, &self.clock.now()))
+    }
+
+    fn write_batch(&self, lines: &[(Option<&Record<'_>>, &str)]) -> io::Result<()> {
+        let now = self.clock.now();
+        let objects: Vec<String> = lines
+            .iter()
+            .map(|(record, value)| self.object(*record, value, &now))
+            .collect();
+
+        let lines: Vec<_> = objects.iter().map(|i| (None, i.as_str())).collect();
+        self.writer.write_batch(&lines)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Appends events to a file as newline-delimited JSON, ready for shippers like Filebeat
+/// or Vector. Size and time based rotation are set up through [`FileOptions`]; headers and
+/// footers become objects with the rendered line as their message.
+pub struct JsonFileAppender {
+    inner: JsonAppender<LogFile>,
+}
+
+impl JsonFileAppender {
+    pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::with_options(path, FileOptions::default())
+    }
+
+    pub fn with_options<P: AsRef<Path>>(path: P, options: FileOptions) -> io::Result<Self> {
+        let mut open_options = OpenOptions::new();
+        open_options.create(true).append(true);
+        let file = options.json().open(path.as_ref(), open_options)?;
+
+        Ok(Self {
+            inner: JsonAppender::new(file).with_clock(options.clock_or_system()),
+        })
+    }
+
+    /// Writes objects in `format` instead of one per line; pretty output is no longer
+    /// newline-delimited, so keep the default for files read by shippers.
+    pub fn with_format(mut self, format: JsonFormat) -> Self {
+        self.inner = self.inner.with_format(format);
+        self
+    }
+}
+
+impl Appender for JsonFileAppender {
+    fn pattern(&self) -> &Pattern {
+        self.inner.pattern()
+    }
+
+    fn write(&self, value: &str) -> io::Result<()> {
+        self.inner.write(value)
+    }
+
+    fn write_record(&self, record: &Record<'_>, value: &str) -> io::Result<()> {
+        self.inner.write_record(record, value)
+    }
+
+    fn write_batch(&self, lines: &[(Option<&Record<'_>>, &str)]) -> io::Result<()> {
+        self.inner.write_batch(lines)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    fn close(&self, _: Instant) -> io::Result<()> {
+        self.inner.writer.writer().close()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::appender::file::{FileOptions, RotationPeriod};
+    use crate::appender::json_file::{JsonAppender, JsonFileAppender, JsonFormat};
+    use crate::appender::non_blocking::NonBlockingAppender;
+    use crate::appender::{Appender, Record};
+    use crate::clock::TestClock;
+    use crate::fields::{EventValue, FieldsVisitor};
+    use crate::pattern::{Pattern, PatternItem};
+    use crate::testing::TestConfig;
+    use crate::ConfigurableLayer;
+    use chrono::{Local, SecondsFormat, TimeZone};
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tracing::field::Visit;
+    use tracing::{info, warn, Level};
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::registry;
+
+    #[test]
+    fn writes_one_object_per_line() {
+        let path = std::env::temp_dir().join(format!(
+            "tracing-configurable-json-{}.log",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let callsite = tracing::callsite! {
+            name: "test",
+            kind: tracing::metadata::Kind::EVENT,
+            target: "my_app",
+            level: Level::WARN,
+            fields: message, user
+        };
+        let metadata = tracing::callsite::Callsite::metadata(callsite);
+        let message = metadata.fields().field("message").unwrap();
+        let user = metadata.fields().field("user").unwrap();
+
+        let mut fields = FieldsVisitor::default();
+        fields.record_str(&message, "said \"hi\"\n");
+        fields.record_str(&user, "alice");
+        fields.record_u64(&user, 7);
+
+        let appender = JsonFileAppender::new(&path).unwrap();
+        appender
+            .write_record(&Record::new(metadata, &fields), "")
+            .unwrap();
+        appender.write("plain").unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(
+            r#""level":"WARN","target":"my_app","message":"said \"hi\"\n","fields":{"user":["alice",7]}}"#
+        ));
+        assert!(lines[1].ends_with(r#""message":"plain"}"#));
+    }
+
+    #[test]
+    fn writes_pretty_colored_objects() {
+        let callsite = tracing::callsite! {
+            name: "test",
+            kind: tracing::metadata::Kind::EVENT,
+            target: "my_app",
+            level: Level::ERROR,
+            fields: message, attempt
+        };
+        let metadata = tracing::callsite::Callsite::metadata(callsite);
+        let message = metadata.fields().field("message").unwrap();
+        let attempt = metadata.fields().field("attempt").unwrap();
+
+        let mut fields = FieldsVisitor::default();
+        fields.record_str(&message, "failed");
+        fields.record_u64(&attempt, 3);
+
+        let appender = JsonAppender::new(Vec::new()).with_format(JsonFormat::pretty());
+        appender
+            .write_record(&Record::new(metadata, &fields), "")
+            .unwrap();
+
+        let content = String::from_utf8(appender.writer.into_inner()).unwrap();
+        assert!(content.starts_with("{\n  \"timestamp\": \""));
+        assert!(content
+            .ends_with("\"message\": \"failed\",\n  \"fields\": {\n    \"attempt\": 3\n  }\n}\n"));
+
+        let appender =
+            JsonAppender::new(Vec::new()).with_format(JsonFormat::pretty().colored(true));
+        appender
+            .write_record(&Record::new(metadata, &fields), "")
+            .unwrap();
+
+        let content = String::from_utf8(appender.writer.into_inner()).unwrap();
+        assert!(content.contains("\x1b[36m\"level\"\x1b[0m: \x1b[31m\"ERROR\"\x1b[0m"));
+        assert!(content.contains("\x1b[33m3\x1b[0m"));
+    }
+
+    #[test]
+    fn writes_resolved_fields() {
+        let appender = Arc::new(JsonAppender::new(Vec::new()));
+        let layer = ConfigurableLayer::new(TestConfig::new(appender.clone()))
+            .with_resolver("requests", |_| Some(EventValue::U64(42)))
+            .with_resolver("user", |_| Some(EventValue::String("resolved".to_string())));
+
+        tracing::subscriber::with_default(registry().with(layer), || {
+            info!(user = "alice", "handled");
+        });
+
+        let appender = Arc::try_unwrap(appender).ok().unwrap();
+        let content = String::from_utf8(appender.writer.into_inner()).unwrap();
+        assert!(
+            content.ends_with("\"fields\":{\"user\":\"alice\",\"requests\":42}}\n"),
+            "{}",
+            content
+        );
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn keeps_event_behind_queue() {
+        let start = Local.with_ymd_and_hms(2024, 3, 1, 8, 30, 0).unwrap();
+        let buf = SharedBuf::default();
+        let appender = NonBlockingAppender::new(JsonAppender::new(buf.clone()), 16);
+        let layer = ConfigurableLayer::new(TestConfig::new(appender))
+            .with_clock(TestClock::new(start))
+            .with_resolver("requests", |_| Some(EventValue::U64(42)));
+
+        // dropping the layer drains the queue
+        tracing::subscriber::with_default(registry().with(layer), || {
+            info!(user = "alice", "queued");
+        });
+
+        let content = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        let timestamp = start.to_rfc3339_opts(SecondsFormat::Micros, true);
+        assert!(content.starts_with(&format!("{{\"timestamp\":\"{}\"", timestamp)));
+        assert!(content.contains("\"message\":\"queued\""), "{}", content);
+        assert!(content.contains("\"user\":\"alice\""), "{}", content);
+        assert!(content.contains("\"requests\":42"), "{}", content);
+    }
+
+    #[test]
+    fn keeps_events_behind_batched_queue() {
+        let buf = SharedBuf::default();
+        let appender =
+            NonBlockingAppender::new(JsonAppender::new(buf.clone()), 16).with_batch_size(8);
+        let layer = ConfigurableLayer::new(TestConfig::new(appender));
+
+        tracing::subscriber::with_default(registry().with(layer), || {
+            for i in 0..5 {
+                warn!(target: "my_app", attempt = i, "retrying");
+            }
+        });
+
+        let content = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 5, "{}", content);
+        for (i, line) in lines.iter().enumerate() {
+            assert!(
+                line.contains(r#""level":"WARN","target":"my_app","message":"retrying""#),
+                "{}",
+                line
+            );
+            assert!(
+                line.ends_with(&format!(r#""fields":{{"attempt":{}}}}}"#, i)),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn writes_batches_and_banners_as_objects() {
+        let path = std::env::temp_dir().join(format!(
+            "tracing-configurable-json-batch-{}.log",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let callsite = tracing::callsite! {
+            name: "test",
+            kind: tracing::metadata::Kind::EVENT,
+            target: "my_app",
+            level: Level::INFO,
+            fields: message, user
+        };
+        let metadata = tracing::callsite::Callsite::metadata(callsite);
+        let message = metadata.fields().field("message").unwrap();
+        let user = metadata.fields().field("user").unwrap();
+
+        let mut fields = FieldsVisitor::default();
+        fields.record_str(&message, "logged in");
+        fields.record_str(&user, "alice");
+        let record = Record::new(metadata, &fields);
+
+        let options = FileOptions::new()
+            .header(Pattern::new(vec![PatternItem::Text("started".to_string())]))
+            .footer(Pattern::new(vec![PatternItem::Text("closed".to_string())]));
+        let appender = JsonFileAppender::with_options(&path, options).unwrap();
+        appender
+            .write_batch(&[(Some(&record), "ignored"), (None, "plain")])
+            .unwrap();
+        drop(appender);
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 4, "{}", content);
+        assert!(lines.iter().all(|i| i.starts_with("{\"timestamp\":\"")));
+        assert!(lines[0].ends_with(r#""message":"started"}"#));
+        assert!(lines[1].ends_with(
+            r#""level":"INFO","target":"my_app","message":"logged in","fields":{"user":"alice"}}"#
+        ));
+        assert!(lines[2].ends_with(r#""message":"plain"}"#));
+        assert!(lines[3].ends_with(r#""message":"closed"}"#));
+    }
+
+    #[test]
+    fn rotates_by_time() {
+        let dir = std::env::temp_dir().join(format!(
+            "tracing-configurable-json-rotate-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let clock = TestClock::new(Local.with_ymd_and_hms(2024, 3, 1, 8, 30, 0).unwrap());
+        let options = FileOptions::new()
+            .rotate_every(RotationPeriod::Hourly)
+            .clock(clock.clone());
+        let appender = JsonFileAppender::with_options(dir.join("app.json"), options).unwrap();
+
+        appender.write("first").unwrap();
+        clock.advance(Duration::from_secs(20 * 60));
+        appender.write("same hour").unwrap();
+        clock.advance(Duration::from_secs(20 * 60));
+        appender.write("next hour").unwrap();
+        drop(appender);
+
+        let mut files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|i| i.unwrap().path())
+            .collect();
+        files.sort();
+        let lines: Vec<usize> = files
+            .iter()
+            .map(|i| std::fs::read_to_string(i).unwrap().lines().count())
+            .collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(files.len(), 2);
+        assert!(files[0].ends_with("app.json"));
+        assert_eq!(lines, [1, 2]);
+    }
+}
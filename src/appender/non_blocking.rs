@@ -0,0 +1,683 @@
+use crate::appender::{Appender, OwnedRecord, Record};
+use crate::diagnostics::{self, Diagnostic};
+use crate::pattern::Pattern;
+use crossbeam_queue::ArrayQueue;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::thread::{JoinHandle, Thread};
+use std::time::{Duration, Instant};
+
+/// How long an idle worker sleeps before re-checking the queue on its own.
+const IDLE_TIMEOUT: Duration = Duration::from_millis(100);
+/// How long `flush` waits for the worker to catch up.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+/// Least time between two reports of dropped lines.
+const OVERFLOW_REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+struct Entry {
+    record: Option<OwnedRecord>,
+    value: String,
+}
+
+struct Shared<A> {
+    inner: A,
+    queue: ArrayQueue<Entry>,
+    dropped: AtomicU64,
+    // lines dropped as of the last report and when it was made
+    reported: Mutex<(u64, Option<Instant>)>,
+    shutdown: AtomicBool,
+    batch_size: AtomicUsize,
+    // queued or being written
+    pending: AtomicUsize,
+    // held by the thread currently writing, pool threads must not interleave
+    draining: AtomicBool,
+    // signalled once `pending` drops to zero
+    idle: Mutex<()>,
+    drained: Condvar,
+}
+
+/// Wakes whoever writes the queue of an appender.
+enum Worker {
+    Dedicated {
+        thread: Thread,
+        // taken by whoever stops the thread first
+        handle: Mutex<Option<JoinHandle<()>>>,
+    },
+    Pool(WorkerPool),
+}
+
+impl Worker {
+    fn wake(&self) {
+        match self {
+            Worker::Dedicated { thread, .. } => thread.unpark(),
+            Worker::Pool(pool) => pool.wake(),
+        }
+    }
+}
+
+/// Hands rendered lines to a background thread that writes them to the inner appender.
+///
+/// Lines are passed through a bounded lock-free queue, so logging threads never contend on
+/// a lock; when the queue is full the line is dropped and the write reports `WouldBlock`.
+/// The layer counts these in its telemetry, the worker reports them as one
+/// [`Diagnostic::QueueOverflow`] every few seconds at most.
+/// Events are queued with a copy of their fields and time, so the inner appender sees the
+/// same record it would without the queue; resolver values are computed before queueing.
+pub struct NonBlockingAppender<A: Appender + Send + Sync + 'static> {
+    shared: Arc<Shared<A>>,
+    worker: Worker,
+}
+
+impl<A: Appender + Send + Sync + 'static> NonBlockingAppender<A> {
+    pub fn new(inner: A, capacity: usize) -> Self {
+        let shared = Self::shared(inner, capacity);
+
+        let worker = {
+            let shared = shared.clone();
+            std::thread::Builder::new()
+                .name("tracing-configurable-appender".to_string())
+                .spawn(move || worker(&shared))
+                .expect("failed to spawn appender worker")
+        };
+
+        Self {
+            shared,
+            worker: Worker::Dedicated {
+                thread: worker.thread().clone(),
+                handle: Mutex::new(Some(worker)),
+            },
+        }
+    }
+
+    /// Like [`new`](Self::new), but the queue is written by the threads of `pool` instead of
+    /// a thread of its own.
+    pub fn with_pool(inner: A, capacity: usize, pool: &WorkerPool) -> Self {
+        let shared = Self::shared(inner, capacity);
+        pool.register(shared.clone());
+
+        Self {
+            shared,
+            worker: Worker::Pool(pool.clone()),
+        }
+    }
+
+    fn shared(inner: A, capacity: usize) -> Arc<Shared<A>> {
+        Arc::new(Shared {
+            inner,
+            queue: ArrayQueue::new(capacity),
+            dropped: AtomicU64::new(0),
+            reported: Mutex::new((0, None)),
+            shutdown: AtomicBool::new(false),
+            batch_size: AtomicUsize::new(1),
+            pending: AtomicUsize::new(0),
+            draining: AtomicBool::new(false),
+            idle: Mutex::new(()),
+            drained: Condvar::new(),
+        })
+    }
+
+    /// Lets the worker hand up to `batch_size` queued lines at once to [`Appender::write_batch`].
+    pub fn with_batch_size(self, batch_size: usize) -> Self {
+        self.shared
+            .batch_size
+            .store(batch_size.max(1), Ordering::Relaxed);
+        self
+    }
+
+    /// Number of lines dropped because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    fn push(&self, entry: Entry) -> io::Result<()> {
+        if self.shared.shutdown.load(Ordering::Acquire) {
+            return Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "appender is closed",
+            ));
+        }
+
+        self.shared.pending.fetch_add(1, Ordering::AcqRel);
+
+        if self.shared.queue.push(entry).is_err() {
+            self.shared.pending.fetch_sub(1, Ordering::AcqRel);
+            self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "appender queue is full",
+            ));
+        }
+
+        self.worker.wake();
+        Ok(())
+    }
+}
+
+impl<A: Appender + Send + Sync + 'static> Appender for NonBlockingAppender<A> {
+    fn pattern(&self) -> &Pattern {
+        self.shared.inner.pattern()
+    }
+
+    fn pattern_for(&self, target: &str) -> &Pattern {
+        self.shared.inner.pattern_for(target)
+    }
+
+    fn write(&self, value: &str) -> io::Result<()> {
+        self.push(Entry {
+            record: None,
+            value: value.to_string(),
+        })
+    }
+
+    fn write_record(&self, record: &Record<'_>, value: &str) -> io::Result<()> {
+        self.push(Entry {
+            // resolvers can't run on the worker, their values are copied with the fields
+            record: Some(OwnedRecord::with_resolved(record)),
+            value: value.to_string(),
+        })
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.flush_timeout(FLUSH_TIMEOUT)
+    }
+
+    /// Stops taking lines, waits until the queue is written and the worker thread stopped,
+    /// then closes the inner appender. If the queue isn't written by `deadline`, the worker
+    /// is left to finish on its own.
+    fn close(&self, deadline: Instant) -> io::Result<()> {
+        self.shared.shutdown.store(true, Ordering::Release);
+        self.wait_drained(deadline)?;
+        self.stop_worker();
+        report_overflow(&self.shared, true);
+        self.shared.inner.close(deadline)
+    }
+}
+
+impl<A: Appender + Send + Sync + 'static> NonBlockingAppender<A> {
+    /// Waits up to `timeout` until everything queued so far is written, then flushes
+    /// the inner appender.
+    pub fn flush_timeout(&self, timeout: Duration) -> io::Result<()> {
+        self.wait_drained(Instant::now() + timeout)?;
+        self.shared.inner.flush()
+    }
+
+    fn wait_drained(&self, deadline: Instant) -> io::Result<()> {
+        let mut idle = self.shared.idle.lock().unwrap();
+
+        while self.shared.pending.load(Ordering::Acquire) > 0 {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "appender queue wasn't drained in time",
+                ));
+            }
+
+            self.worker.wake();
+            idle = self
+                .shared
+                .drained
+                .wait_timeout(idle, deadline - now)
+                .unwrap()
+                .0;
+        }
+
+        Ok(())
+    }
+
+    /// Joins the dedicated worker, which exits once it finds the queue empty.
+    fn stop_worker(&self) {
+        if let Worker::Dedicated { thread, handle } = &self.worker {
+            if let Some(handle) = handle.lock().unwrap().take() {
+                thread.unpark();
+                join(handle);
+            }
+        }
+    }
+}
+
+impl<A: Appender + Send + Sync + 'static> Drop for NonBlockingAppender<A> {
+    fn drop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::Release);
+
+        if let Worker::Pool(_) = &self.worker {
+            // the pool forgets the queue once it's dropped, write what's left first
+            drain(&self.shared);
+            if let Err(error) = self.flush_timeout(FLUSH_TIMEOUT) {
+                diagnostics::report(Diagnostic::FlushError { error: &error });
+            }
+        }
+
+        self.stop_worker();
+        report_overflow(&self.shared, true);
+    }
+}
+
+fn worker<A: Appender>(shared: &Shared<A>) {
+    loop {
+        drain(shared);
+
+        if shared.shutdown.load(Ordering::Acquire) && shared.queue.is_empty() {
+            break;
+        }
+
+        std::thread::park_timeout(IDLE_TIMEOUT);
+    }
+}
+
+/// Writes everything queued, unless another thread is already doing so.
+fn drain<A: Appender>(shared: &Shared<A>) {
+    // a thread woken for a line pushed after the queue ran empty leaves while `draining` is
+    // still set, so whoever clears it checks the queue once more
+    while !shared.draining.swap(true, Ordering::SeqCst) {
+        write_queued(shared);
+        shared.draining.store(false, Ordering::SeqCst);
+
+        if shared.queue.is_empty() {
+            break;
+        }
+    }
+
+    if shared.pending.load(Ordering::Acquire) == 0 {
+        // under the lock, so a waiter can't miss it between its check and its wait
+        let _idle = shared.idle.lock().unwrap();
+        shared.drained.notify_all();
+    }
+}
+
+fn write_queued<A: Appender>(shared: &Shared<A>) {
+    let batch_size = shared.batch_size.load(Ordering::Relaxed);
+
+    if batch_size > 1 {
+        let mut batch = Vec::new();
+
+        loop {
+            batch.extend(std::iter::from_fn(|| shared.queue.pop()).take(batch_size));
+
+            if batch.is_empty() {
+                break;
+            }
+
+            let records = batch
+                .iter()
+                .map(|i| i.record.as_ref().map(OwnedRecord::record))
+                .collect::<Vec<_>>();
+            let lines = records
+                .iter()
+                .zip(&batch)
+                .map(|(record, entry)| (record.as_ref(), entry.value.as_str()))
+                .collect::<Vec<_>>();
+            if let Err(error) = shared.inner.write_batch(&lines) {
+                diagnostics::report(Diagnostic::AppenderError {
+                    target: None,
+                    error: &error,
+                });
+            }
+            shared.pending.fetch_sub(batch.len(), Ordering::AcqRel);
+            batch.clear();
+        }
+    } else {
+        while let Some(entry) = shared.queue.pop() {
+            let result = match &entry.record {
+                Some(record) => shared.inner.write_record(&record.record(), &entry.value),
+                None => shared.inner.write(&entry.value),
+            };
+
+            if let Err(error) = result {
+                diagnostics::report(Diagnostic::AppenderError {
+                    target: entry.record.as_ref().map(|i| i.metadata().target()),
+                    error: &error,
+                });
+            }
+            shared.pending.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+
+    report_overflow(shared, false);
+}
+
+/// Waits for a worker thread to stop, reporting it if it panicked.
+fn join(thread: JoinHandle<()>) {
+    let name = thread.thread().name().unwrap_or("<unnamed>").to_string();
+
+    if let Err(payload) = thread.join() {
+        let message = if let Some(v) = payload.downcast_ref::<&str>() {
+            v
+        } else if let Some(v) = payload.downcast_ref::<String>() {
+            v
+        } else {
+            "Box<dyn Any>"
+        };
+
+        diagnostics::report(Diagnostic::WorkerPanicked {
+            thread: &name,
+            message,
+        });
+    }
+}
+
+/// Reports the lines dropped since the last report, unless one was made less than
+/// `OVERFLOW_REPORT_INTERVAL` ago and this isn't the final one.
+fn report_overflow<A>(shared: &Shared<A>, last: bool) {
+    let dropped = shared.dropped.load(Ordering::Relaxed);
+    let mut reported = shared.reported.lock().unwrap();
+    let (count, at) = &mut *reported;
+
+    let recently = at.is_some_and(|i| i.elapsed() < OVERFLOW_REPORT_INTERVAL);
+    if dropped == *count || (recently && !last) {
+        return;
+    }
+
+    diagnostics::report(Diagnostic::QueueOverflow {
+        dropped: dropped - *count,
+    });
+    *count = dropped;
+    *at = Some(Instant::now());
+}
+
+trait Queue: Send + Sync {
+    fn drain(&self);
+}
+
+impl<A: Appender + Send + Sync> Queue for Shared<A> {
+    fn drain(&self) {
+        drain(self)
+    }
+}
+
+struct PoolShared {
+    queues: Mutex<Vec<Weak<dyn Queue>>>,
+    shutdown: AtomicBool,
+}
+
+struct PoolThreads {
+    shared: Arc<PoolShared>,
+    threads: Vec<JoinHandle<()>>,
+    next: AtomicUsize,
+}
+
+impl Drop for PoolThreads {
+    fn drop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::Release);
+
+        for thread in self.threads.drain(..) {
+            thread.thread().unpark();
+            join(thread);
+        }
+    }
+}
+
+/// Background threads shared by several [`NonBlockingAppender`]s.
+///
+/// Each appender keeps its own queue, a queue is written by one pool thread at a time so
+/// lines keep their order. The threads stop once the pool and every appender using it
+/// are dropped.
+#[derive(Clone)]
+pub struct WorkerPool {
+    threads: Arc<PoolThreads>,
+}
+
+impl WorkerPool {
+    pub fn new(threads: usize) -> Self {
+        let shared = Arc::new(PoolShared {
+            queues: Mutex::new(Vec::new()),
+            shutdown: AtomicBool::new(false),
+        });
+
+        let threads = (0..threads.max(1))
+            .map(|i| {
+                let shared = shared.clone();
+                std::thread::Builder::new()
+                    .name(format!("tracing-configurable-pool-{}", i))
+                    .spawn(move || pool_worker(&shared))
+                    .expect("failed to spawn appender pool worker")
+            })
+            .collect();
+
+        Self {
+            threads: Arc::new(PoolThreads {
+                shared,
+                threads,
+                next: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    fn register(&self, queue: Arc<dyn Queue>) {
+        let mut queues = self.threads.shared.queues.lock().unwrap();
+        queues.retain(|i| i.strong_count() > 0);
+        queues.push(Arc::downgrade(&queue));
+    }
+
+    fn wake(&self) {
+        let threads = &self.threads.threads;
+        let next = self.threads.next.fetch_add(1, Ordering::Relaxed);
+        threads[next % threads.len()].thread().unpark();
+    }
+}
+
+fn pool_worker(shared: &PoolShared) {
+    while !shared.shutdown.load(Ordering::Acquire) {
+        let queues: Vec<_> = shared
+            .queues
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect();
+
+        for queue in queues {
+            queue.drain();
+        }
+
+        std::thread::park_timeout(IDLE_TIMEOUT);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::appender::non_blocking::{NonBlockingAppender, WorkerPool, IDLE_TIMEOUT};
+    use crate::appender::{Appender, Record};
+    use crate::pattern::Pattern;
+    use crate::testing::TestConfig;
+    use crate::ConfigurableLayer;
+    use std::sync::mpsc::{self, Sender};
+    use std::sync::{Arc, Mutex};
+    use tracing::info;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::registry;
+
+    struct VecAppender {
+        pattern: Pattern,
+        lines: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Appender for VecAppender {
+        fn pattern(&self) -> &Pattern {
+            &self.pattern
+        }
+
+        fn write(&self, value: &str) -> std::io::Result<()> {
+            self.lines.lock().unwrap().push(value.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn drains_queue_on_drop() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let appender = NonBlockingAppender::new(
+            VecAppender {
+                pattern: Pattern::new(vec![]),
+                lines: lines.clone(),
+            },
+            16,
+        );
+
+        for i in 0..10 {
+            appender.write(&i.to_string()).unwrap();
+        }
+        drop(appender);
+
+        let expected = (0..10).map(|i| i.to_string()).collect::<Vec<_>>();
+        assert_eq!(*lines.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn pool_writes_every_queue() {
+        let pool = WorkerPool::new(1);
+        let lines = Arc::new(Mutex::new(Vec::new()));
+
+        let appenders = (0..3)
+            .map(|_| {
+                NonBlockingAppender::with_pool(
+                    VecAppender {
+                        pattern: Pattern::new(vec![]),
+                        lines: lines.clone(),
+                    },
+                    16,
+                    &pool,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        for (i, appender) in appenders.iter().enumerate() {
+            appender.write(&i.to_string()).unwrap();
+        }
+        for appender in &appenders {
+            appender.flush().unwrap();
+        }
+
+        let mut written = lines.lock().unwrap().clone();
+        written.sort();
+        assert_eq!(written, ["0", "1", "2"]);
+    }
+
+    struct ChannelAppender {
+        pattern: Pattern,
+        sender: Mutex<Sender<String>>,
+    }
+
+    impl Appender for ChannelAppender {
+        fn pattern(&self) -> &Pattern {
+            &self.pattern
+        }
+
+        fn write(&self, value: &str) -> std::io::Result<()> {
+            let _ = self.sender.lock().unwrap().send(value.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn pool_writes_lines_pushed_while_draining() {
+        let pool = WorkerPool::new(2);
+        let (sender, receiver) = mpsc::channel();
+        let appender = NonBlockingAppender::with_pool(
+            ChannelAppender {
+                pattern: Pattern::new(vec![]),
+                sender: Mutex::new(sender),
+            },
+            16,
+            &pool,
+        );
+        // both threads park first, so neither has a wakeup left over
+        std::thread::sleep(IDLE_TIMEOUT / 5);
+
+        {
+            // the thread draining "0" finds the queue empty and then waits for the lock, the
+            // other thread is woken for "1" while the first one still counts as draining
+            let _reported = appender.shared.reported.lock().unwrap();
+            appender.write("0").unwrap();
+            assert_eq!(receiver.recv_timeout(IDLE_TIMEOUT).unwrap(), "0");
+            std::thread::sleep(IDLE_TIMEOUT / 5);
+            appender.write("1").unwrap();
+            std::thread::sleep(IDLE_TIMEOUT / 5);
+        }
+
+        let line = receiver.recv_timeout(IDLE_TIMEOUT / 2).unwrap();
+        assert_eq!(line, "1");
+    }
+
+    #[test]
+    fn counts_overflow_without_per_event_errors() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let appender = Arc::new(NonBlockingAppender::new(
+            VecAppender {
+                pattern: Pattern::new(vec![]),
+                lines: lines.clone(),
+            },
+            1,
+        ));
+        let layer = ConfigurableLayer::new(TestConfig::new(appender.clone()));
+        let telemetry = layer.telemetry();
+
+        {
+            // the worker blocks on the first line, the queue holds one more
+            let _blocked = lines.lock().unwrap();
+            tracing::subscriber::with_default(registry().with(layer), || {
+                for i in 0..5 {
+                    info!("line {}", i);
+                }
+            });
+        }
+
+        let snapshot = telemetry.snapshot(0);
+        assert!(snapshot.overflowed >= 3);
+        assert_eq!(snapshot.overflowed, appender.dropped());
+        assert_eq!(snapshot.dropped, snapshot.overflowed);
+        assert_eq!(snapshot.appender_errors, 0);
+    }
+
+    struct BatchAppender {
+        pattern: Pattern,
+        batches: Arc<Mutex<Vec<Vec<String>>>>,
+    }
+
+    impl Appender for BatchAppender {
+        fn pattern(&self) -> &Pattern {
+            &self.pattern
+        }
+
+        fn write(&self, value: &str) -> std::io::Result<()> {
+            self.write_batch(&[(None, value)])
+        }
+
+        fn write_batch(&self, lines: &[(Option<&Record<'_>>, &str)]) -> std::io::Result<()> {
+            let batch = lines.iter().map(|(_, i)| i.to_string()).collect();
+            self.batches.lock().unwrap().push(batch);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn hands_queued_lines_over_in_batches() {
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let appender = NonBlockingAppender::new(
+            BatchAppender {
+                pattern: Pattern::new(vec![]),
+                batches: batches.clone(),
+            },
+            16,
+        )
+        .with_batch_size(4);
+
+        {
+            // lines queue up while the worker waits for the lock
+            let _blocked = batches.lock().unwrap();
+            for i in 0..10 {
+                appender.write(&i.to_string()).unwrap();
+            }
+        }
+        drop(appender);
+
+        let batches = batches.lock().unwrap();
+        assert!(batches.len() < 10);
+        assert!(batches.iter().all(|i| !i.is_empty() && i.len() <= 4));
+
+        let lines = batches.concat();
+        let expected = (0..10).map(|i| i.to_string()).collect::<Vec<_>>();
+        assert_eq!(lines, expected);
+    }
+}
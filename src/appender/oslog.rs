@@ -0,0 +1,78 @@
+use crate::appender::{Appender, Record};
+use crate::pattern::Pattern;
+use oslog::OsLog;
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, RwLock};
+use tracing::Level;
+
+/// Forwards events to Apple's unified logging system so they show up in Console.app.
+///
+/// By default the subsystem is the root of the event target and the category is the
+/// rest of it, i.e. `my_app::net::http` logs to subsystem `my_app`, category `net::http`.
+pub struct OsLogAppender {
+    pattern: Pattern,
+    subsystem: Option<String>,
+    logs: RwLock<HashMap<String, Arc<OsLog>>>,
+}
+
+impl OsLogAppender {
+    pub fn new(pattern: Pattern) -> Self {
+        Self {
+            pattern,
+            subsystem: None,
+            logs: Default::default(),
+        }
+    }
+
+    /// Uses a fixed subsystem (e.g. `com.example.app`) and the full target as the category.
+    pub fn with_subsystem<S: Into<String>>(mut self, subsystem: S) -> Self {
+        self.subsystem = Some(subsystem.into());
+        self
+    }
+
+    fn log(&self, target: &str) -> Arc<OsLog> {
+        if let Some(log) = self.logs.read().unwrap().get(target) {
+            return log.clone();
+        }
+
+        let (subsystem, category) = match &self.subsystem {
+            Some(subsystem) => (subsystem.as_str(), target),
+            None => target.split_once("::").unwrap_or((target, "default")),
+        };
+
+        self.logs
+            .write()
+            .unwrap()
+            .entry(target.to_string())
+            .or_insert_with(|| Arc::new(OsLog::new(subsystem, category)))
+            .clone()
+    }
+}
+
+impl Appender for OsLogAppender {
+    fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+
+    fn write(&self, value: &str) -> io::Result<()> {
+        OsLog::global().default(value);
+        Ok(())
+    }
+
+    fn write_record(&self, record: &Record<'_>, value: &str) -> io::Result<()> {
+        self.log(record.target())
+            .with_level(os_log_level(record.level()), value);
+        Ok(())
+    }
+}
+
+fn os_log_level(level: &Level) -> oslog::Level {
+    match *level {
+        Level::TRACE => oslog::Level::Debug,
+        Level::DEBUG => oslog::Level::Info,
+        Level::INFO => oslog::Level::Default,
+        Level::WARN => oslog::Level::Error,
+        Level::ERROR => oslog::Level::Fault,
+    }
+}
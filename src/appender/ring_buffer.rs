@@ -0,0 +1,341 @@
+use crate::appender::{Appender, Record};
+use crate::clock::{self, Clock};
+use crate::fields::EventValue;
+use crate::pattern::Pattern;
+use chrono::{DateTime, Local};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::Level;
+
+/// An event kept by [`RingBufferAppender`].
+#[derive(Debug, Clone)]
+pub struct StoredRecord {
+    pub time: DateTime<Local>,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+    pub fields: Vec<(&'static str, EventValue)>,
+    /// The line as rendered by the appender's pattern.
+    pub rendered: String,
+}
+
+impl StoredRecord {
+    /// First value recorded for `name`.
+    pub fn field(&self, name: &str) -> Option<&EventValue> {
+        self.fields
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+    }
+}
+
+type FieldPredicate = Box<dyn Fn(&EventValue) -> bool + Send + Sync>;
+
+/// Filter for [`RingBufferAppender::query`], all conditions have to match.
+#[derive(Default)]
+pub struct Query {
+    level: Option<Level>,
+    target: Option<String>,
+    since: Option<DateTime<Local>>,
+    until: Option<DateTime<Local>>,
+    within: Option<Duration>,
+    fields: Vec<(String, FieldPredicate)>,
+    limit: Option<usize>,
+}
+
+impl Query {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records at `level` or more severe.
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Records whose target matches `glob`, where `*` matches any run of characters,
+    /// e.g. `my_app::*` or `*::db`.
+    pub fn target<S: Into<String>>(mut self, glob: S) -> Self {
+        self.target = Some(glob.into());
+        self
+    }
+
+    pub fn since(mut self, time: DateTime<Local>) -> Self {
+        self.since = Some(time);
+        self
+    }
+
+    pub fn until(mut self, time: DateTime<Local>) -> Self {
+        self.until = Some(time);
+        self
+    }
+
+    /// Records from the last `duration`, e.g. `?since=5m`, as of the appender's clock when
+    /// the query runs.
+    pub fn within(mut self, duration: Duration) -> Self {
+        self.within = Some(duration);
+        self
+    }
+
+    /// Records with a field `name` for which `predicate` holds.
+    pub fn field<S, F>(mut self, name: S, predicate: F) -> Self
+    where
+        S: Into<String>,
+        F: Fn(&EventValue) -> bool + Send + Sync + 'static,
+    {
+        self.fields.push((name.into(), Box::new(predicate)));
+        self
+    }
+
+    /// Records with a field `name` that displays as `value`.
+    pub fn field_eq<S: Into<String>, V: ToString>(self, name: S, value: V) -> Self {
+        let value = value.to_string();
+        self.field(name, move |i| i.to_string() == value)
+    }
+
+    /// Keeps only the newest `limit` matching records.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Start of the `within` window ending at `now`.
+    fn window_start(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        self.within.map(|within| {
+            chrono::Duration::from_std(within)
+                .ok()
+                .and_then(|i| now.checked_sub_signed(i))
+                .unwrap_or(DateTime::<Local>::MIN_UTC.into())
+        })
+    }
+
+    fn matches(&self, record: &StoredRecord, window_start: Option<DateTime<Local>>) -> bool {
+        self.level.is_none_or(|i| record.level <= i)
+            && self
+                .target
+                .as_deref()
+                .is_none_or(|i| glob_matches(i, &record.target))
+            && self.since.is_none_or(|i| record.time >= i)
+            && self.until.is_none_or(|i| record.time <= i)
+            && window_start.is_none_or(|i| record.time >= i)
+            && self.fields.iter().all(|(name, predicate)| {
+                record
+                    .fields
+                    .iter()
+                    .any(|(key, value)| key == name && predicate(value))
+            })
+    }
+}
+
+fn glob_matches(glob: &str, value: &str) -> bool {
+    let Some((prefix, rest)) = glob.split_once('*') else {
+        return glob == value;
+    };
+    let Some(mut value) = value.strip_prefix(prefix) else {
+        return false;
+    };
+
+    let mut parts = rest.split('*').peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            return value.ends_with(part);
+        }
+
+        match value.find(part) {
+            Some(idx) => value = &value[idx + part.len()..],
+            None => return false,
+        }
+    }
+
+    true
+}
+
+/// Keeps the latest `capacity` events in memory and answers queries over them, e.g. to
+/// serve a `/debug/logs?level=error&since=5m` endpoint.
+///
+/// Clones share the stored events, so one clone can be handed to the config while the
+/// application queries another.
+#[derive(Clone)]
+pub struct RingBufferAppender {
+    pattern: Arc<Pattern>,
+    capacity: usize,
+    records: Arc<Mutex<VecDeque<StoredRecord>>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl RingBufferAppender {
+    pub fn new(pattern: Pattern, capacity: usize) -> Self {
+        Self {
+            pattern: Arc::new(pattern),
+            capacity: capacity.max(1),
+            records: Default::default(),
+            clock: clock::system(),
+        }
+    }
+
+    /// Clock timestamping lines written without an event and ending [`Query::within`]
+    /// windows; events carry their own time. Give it the layer's clock. Defaults to
+    /// [`clock::system`].
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Matching records, oldest first.
+    pub fn query(&self, query: &Query) -> Vec<StoredRecord> {
+        let window_start = query.window_start(self.clock.now());
+        let records = self.records.lock().unwrap();
+        let mut matched = records
+            .iter()
+            .rev()
+            .filter(|i| query.matches(i, window_start))
+            .take(query.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        matched.reverse();
+        matched
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.records.lock().unwrap().clear();
+    }
+
+    fn push(&self, record: StoredRecord) {
+        let mut records = self.records.lock().unwrap();
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+
+        records.push_back(record);
+    }
+}
+
+impl Appender for RingBufferAppender {
+    fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+
+    fn write(&self, value: &str) -> std::io::Result<()> {
+        self.push(StoredRecord {
+            time: self.clock.now(),
+            level: Level::INFO,
+            target: String::new(),
+            message: value.to_string(),
+            fields: vec![],
+            rendered: value.to_string(),
+        });
+
+        Ok(())
+    }
+
+    fn write_record(&self, record: &Record<'_>, value: &str) -> std::io::Result<()> {
+        let fields = record
+            .fields()
+            .values()
+            .flat_map(|(key, values)| values.iter().map(move |v| (key, v.clone())))
+            .collect();
+
+        self.push(StoredRecord {
+            time: record.time().unwrap_or_else(|| self.clock.now()),
+            level: *record.level(),
+            target: record.target().to_string(),
+            message: record.fields().message().to_string(),
+            fields,
+            rendered: value.to_string(),
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::appender::ring_buffer::{glob_matches, Query, RingBufferAppender};
+    use crate::clock::TestClock;
+    use crate::fields::EventValue;
+    use crate::pattern::Pattern;
+    use crate::testing::TestConfig;
+    use crate::ConfigurableLayer;
+    use chrono::{Local, TimeZone};
+    use std::time::Duration;
+    use tracing::{error, info, warn, Level};
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::registry;
+
+    #[test]
+    fn matches_globs() {
+        assert!(glob_matches("my_app::*", "my_app::db"));
+        assert!(glob_matches("*::db", "my_app::db"));
+        assert!(glob_matches("my_*::*b", "my_app::db"));
+        assert!(glob_matches("my_app", "my_app"));
+        assert!(!glob_matches("my_app", "my_app::db"));
+        assert!(!glob_matches("*::http", "my_app::db"));
+    }
+
+    #[test]
+    fn queries_records() {
+        let store = RingBufferAppender::new(Pattern::new(Vec::new()), 3);
+        let subscriber = registry().with(ConfigurableLayer::new(TestConfig::new(store.clone())));
+
+        tracing::subscriber::with_default(subscriber, || {
+            info!(target: "my_app::http", "evicted");
+            warn!(target: "my_app::db", elapsed_ms = 250u64, "slow query");
+            error!(target: "my_app::db", elapsed_ms = 10u64, "query failed");
+            info!(target: "my_app::http", "request");
+        });
+
+        assert_eq!(store.len(), 3);
+
+        let errors = store.query(&Query::new().level(Level::WARN).target("*::db"));
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].message, "slow query");
+
+        let slow = store.query(&Query::new().field(
+            "elapsed_ms",
+            |i| matches!(i, EventValue::U64(v) if *v > 100),
+        ));
+        assert_eq!(slow.len(), 1);
+
+        let latest = store.query(&Query::new().within(Duration::from_secs(60)).limit(1));
+        assert_eq!(latest[0].message, "request");
+    }
+
+    #[test]
+    fn queries_by_clock_time() {
+        let start = Local.with_ymd_and_hms(2024, 3, 1, 8, 30, 0).unwrap();
+        let clock = TestClock::new(start);
+        let store = RingBufferAppender::new(Pattern::new(Vec::new()), 8).with_clock(clock.clone());
+        let layer =
+            ConfigurableLayer::new(TestConfig::new(store.clone())).with_clock(clock.clone());
+
+        tracing::subscriber::with_default(registry().with(layer), || {
+            info!("early");
+            clock.advance(Duration::from_secs(120));
+            info!("late");
+        });
+
+        let all = store.query(&Query::new());
+        assert_eq!(all[0].time, start);
+        assert_eq!(all[1].time, start + chrono::Duration::seconds(120));
+
+        let recent = store.query(&Query::new().within(Duration::from_secs(60)));
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].message, "late");
+
+        clock.advance(Duration::from_secs(120));
+        assert!(store
+            .query(&Query::new().within(Duration::from_secs(60)))
+            .is_empty());
+    }
+}
@@ -0,0 +1,114 @@
+use crate::appender::{Appender, Record};
+use crate::pattern::Pattern;
+use std::borrow::Cow;
+use std::io;
+use std::time::Instant;
+
+/// Removes ANSI escape sequences before handing lines to the inner appender, so a colored
+/// pattern can be shared between a terminal and a file.
+pub struct StripAnsiAppender<A: Appender> {
+    inner: A,
+}
+
+impl<A: Appender> StripAnsiAppender<A> {
+    pub fn new(inner: A) -> Self {
+        Self { inner }
+    }
+
+    pub fn into_inner(self) -> A {
+        self.inner
+    }
+}
+
+impl<A: Appender> Appender for StripAnsiAppender<A> {
+    fn pattern(&self) -> &Pattern {
+        self.inner.pattern()
+    }
+
+    fn pattern_for(&self, target: &str) -> &Pattern {
+        self.inner.pattern_for(target)
+    }
+
+    fn write(&self, value: &str) -> io::Result<()> {
+        self.inner.write(&strip_ansi(value))
+    }
+
+    fn write_record(&self, record: &Record<'_>, value: &str) -> io::Result<()> {
+        self.inner.write_record(record, &strip_ansi(value))
+    }
+
+    fn write_batch(&self, lines: &[(Option<&Record<'_>>, &str)]) -> io::Result<()> {
+        let values: Vec<Cow<str>> = lines.iter().map(|(_, i)| strip_ansi(i)).collect();
+        let lines: Vec<_> = lines
+            .iter()
+            .zip(&values)
+            .map(|((record, _), value)| (*record, value.as_ref()))
+            .collect();
+        self.inner.write_batch(&lines)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    fn close(&self, deadline: Instant) -> io::Result<()> {
+        self.inner.close(deadline)
+    }
+}
+
+/// Removes CSI (`ESC [ ... m`), OSC (`ESC ] ... BEL`) and two-byte escape sequences.
+pub fn strip_ansi(value: &str) -> Cow<'_, str> {
+    if !value.contains('\x1b') {
+        return Cow::Borrowed(value);
+    }
+
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            // CSI: parameter and intermediate bytes, then a final byte in '@'..='~'
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: terminated by BEL or ST (`ESC \`)
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Cow::Owned(out)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::appender::strip_ansi::strip_ansi;
+
+    #[test]
+    fn strips_escape_sequences() {
+        assert_eq!(strip_ansi("plain"), "plain");
+        assert_eq!(
+            strip_ansi("\x1b[1;31mERROR\x1b[0m \x1b]8;;http://x\x07link\x1b]8;;\x1b\\ done\x1b7"),
+            "ERROR link done"
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use crate::appender::{Appender, Record};
+use crate::config::matcher::TargetMatcher;
+use crate::pattern::Pattern;
+use std::io;
+use std::time::Instant;
+
+/// Renders events of selected targets with their own pattern before they reach the inner
+/// appender, e.g. a compact pattern for `hyper::*` next to a verbose one for `my_app::*`.
+///
+/// Overrides are resolved with a [`TargetMatcher`], so the most specific rule wins; other
+/// targets use the inner appender's pattern.
+pub struct TargetPatterns<A: Appender> {
+    inner: A,
+    overrides: TargetMatcher<Pattern>,
+}
+
+impl<A: Appender> TargetPatterns<A> {
+    pub fn new(inner: A) -> Self {
+        Self {
+            inner,
+            overrides: TargetMatcher::new(),
+        }
+    }
+
+    /// Uses `pattern` for `target` and the modules below it.
+    pub fn with_override<S: AsRef<str>>(mut self, target: S, pattern: Pattern) -> Self {
+        self.overrides.insert(target, pattern);
+        self
+    }
+}
+
+impl<A: Appender> Appender for TargetPatterns<A> {
+    fn pattern(&self) -> &Pattern {
+        self.inner.pattern()
+    }
+
+    fn pattern_for(&self, target: &str) -> &Pattern {
+        self.overrides
+            .get(target)
+            .unwrap_or_else(|| self.inner.pattern_for(target))
+    }
+
+    fn write(&self, value: &str) -> io::Result<()> {
+        self.inner.write(value)
+    }
+
+    fn write_record(&self, record: &Record<'_>, value: &str) -> io::Result<()> {
+        self.inner.write_record(record, value)
+    }
+
+    fn write_batch(&self, lines: &[(Option<&Record<'_>>, &str)]) -> io::Result<()> {
+        self.inner.write_batch(lines)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    fn close(&self, deadline: Instant) -> io::Result<()> {
+        self.inner.close(deadline)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::appender::target_patterns::TargetPatterns;
+    use crate::pattern::{Pattern, PatternItem, Placeholder, PlaceholderType};
+    use crate::testing::{CaptureAppender, TestConfig};
+    use crate::ConfigurableLayer;
+    use std::collections::HashMap;
+    use tracing::info;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::registry;
+
+    #[test]
+    fn overrides_pattern_per_target() {
+        let placeholder =
+            |ty| PatternItem::Placeholder(Placeholder::new(ty, HashMap::new(), vec![]));
+        let with_target = Pattern::new(vec![
+            placeholder(PlaceholderType::Target),
+            PatternItem::Text(": ".to_string()),
+            placeholder(PlaceholderType::Message),
+        ]);
+
+        let capture = CaptureAppender::new();
+        let appender = TargetPatterns::new(capture.clone()).with_override("hyper", with_target);
+
+        let layer = ConfigurableLayer::new(TestConfig::new(appender));
+        tracing::subscriber::with_default(registry().with(layer), || {
+            info!(target: "hyper::client", "connected");
+            info!(target: "my_app", "started");
+        });
+
+        let rendered: Vec<String> = capture.events().into_iter().map(|i| i.rendered).collect();
+        assert_eq!(rendered, ["hyper::client: connected", "started"]);
+    }
+}
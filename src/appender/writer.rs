@@ -0,0 +1,138 @@
+use crate::appender::{Appender, Record};
+use crate::pattern::Pattern;
+use std::io::{self, IoSlice, Write};
+use std::sync::{Mutex, MutexGuard};
+
+/// Writes newline-terminated lines to any [`Write`] implementation (file, socket, stdout...).
+///
+/// Batches are written with vectored IO, so a batch usually costs a single syscall.
+pub struct WriterAppender<W: Write + Send> {
+    pattern: Pattern,
+    writer: Mutex<W>,
+}
+
+impl<W: Write + Send> WriterAppender<W> {
+    pub fn new(pattern: Pattern, writer: W) -> Self {
+        Self {
+            pattern,
+            writer: Mutex::new(writer),
+        }
+    }
+
+    pub(crate) fn writer(&self) -> MutexGuard<'_, W> {
+        self.writer.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<W: Write + Send> Appender for WriterAppender<W> {
+    fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+
+    fn write(&self, value: &str) -> io::Result<()> {
+        self.write_batch(&[(None, value)])
+    }
+
+    fn write_batch(&self, lines: &[(Option<&Record<'_>>, &str)]) -> io::Result<()> {
+        let mut slices = Vec::with_capacity(lines.len() * 2);
+        for (_, value) in lines {
+            slices.push(IoSlice::new(value.as_bytes()));
+            slices.push(IoSlice::new(b"\n"));
+        }
+
+        let mut slices = &mut slices[..];
+        let mut writer = self.writer.lock().unwrap();
+
+        while !slices.is_empty() {
+            match writer.write_vectored(slices) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => IoSlice::advance_slices(&mut slices, n),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        writer.flush()
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.writer.lock().unwrap().flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::appender::writer::WriterAppender;
+    use crate::appender::Appender;
+    use crate::pattern::Pattern;
+    use std::io::{self, IoSlice, Write};
+
+    /// Takes at most `limit` bytes per call, like a socket with a small send buffer.
+    struct ShortWriter {
+        limit: usize,
+        calls: usize,
+        written: Vec<u8>,
+    }
+
+    impl Write for ShortWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.write_vectored(&[IoSlice::new(buf)])
+        }
+
+        fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+            self.calls += 1;
+
+            let mut written = 0;
+            for buf in bufs {
+                let len = buf.len().min(self.limit - written);
+                self.written.extend_from_slice(&buf[..len]);
+                written += len;
+            }
+
+            Ok(written)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writes_batch_with_few_calls() {
+        let appender = WriterAppender::new(
+            Pattern::new(vec![]),
+            ShortWriter {
+                limit: usize::MAX,
+                calls: 0,
+                written: Vec::new(),
+            },
+        );
+        appender
+            .write_batch(&[(None, "first"), (None, "second"), (None, "third")])
+            .unwrap();
+
+        let writer = appender.into_inner();
+        assert_eq!(writer.calls, 1);
+        assert_eq!(writer.written, b"first\nsecond\nthird\n");
+
+        let appender = WriterAppender::new(
+            Pattern::new(vec![]),
+            ShortWriter {
+                limit: 4,
+                calls: 0,
+                written: Vec::new(),
+            },
+        );
+        appender
+            .write_batch(&[(None, "first"), (None, "second"), (None, "third")])
+            .unwrap();
+
+        let writer = appender.into_inner();
+        assert_eq!(writer.calls, 5);
+        assert_eq!(writer.written, b"first\nsecond\nthird\n");
+    }
+}
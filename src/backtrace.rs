@@ -0,0 +1,131 @@
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::fmt::Write;
+
+/// Frames of the logging machinery itself, hidden from short backtraces.
+const INTERNAL_PREFIXES: &[&str] = &[
+    "std::",
+    "core::",
+    "alloc::",
+    "tracing::",
+    "tracing_core::",
+    "tracing_subscriber::",
+    "tracing_configurable::",
+    "__rust",
+    "rust_begin_unwind",
+];
+
+/// Captures a backtrace of the current thread, rendered one frame per line.
+///
+/// Unless `full` is set, frames of the standard library and of the tracing stack are left
+/// out; `limit` caps the number of frames.
+pub(crate) fn capture(full: bool, limit: Option<usize>) -> Option<String> {
+    let backtrace = Backtrace::force_capture();
+    if backtrace.status() != BacktraceStatus::Captured {
+        return None;
+    }
+
+    let text = backtrace.to_string();
+    let mut out = String::new();
+    let mut frames = 0;
+    let mut keep = false;
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+
+        // frames look like `  12: symbol`, followed by an optional `at file:line`
+        if let Some((idx, symbol)) = trimmed.split_once(": ") {
+            if idx.chars().all(|i| i.is_ascii_digit()) {
+                keep = full || !is_internal(symbol);
+                if keep {
+                    if limit.is_some_and(|i| frames >= i) {
+                        break;
+                    }
+
+                    frames += 1;
+                }
+            }
+        }
+
+        if keep {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            let _ = write!(out, "{}", line);
+        }
+    }
+
+    Some(out).filter(|i| !i.is_empty())
+}
+
+fn is_internal(symbol: &str) -> bool {
+    let symbol = symbol.trim_start_matches('<');
+    INTERNAL_PREFIXES.iter().any(|i| symbol.starts_with(i))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::backtrace::is_internal;
+    use crate::pattern::{Pattern, PatternItem, Placeholder, PlaceholderType, PlaceholderValue};
+    use crate::testing::{CaptureAppender, TestConfig};
+    use crate::ConfigurableLayer;
+    use std::collections::HashMap;
+    use tracing::{error, info, warn};
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::registry;
+
+    fn backtrace_of<F: FnOnce()>(placeholder: Placeholder, log: F) -> Vec<String> {
+        let capture = CaptureAppender::with_pattern(Pattern::new(vec![PatternItem::Placeholder(
+            placeholder,
+        )]));
+        let layer = ConfigurableLayer::new(TestConfig::new(capture.clone()));
+        tracing::subscriber::with_default(registry().with(layer), log);
+
+        capture.events().into_iter().map(|i| i.rendered).collect()
+    }
+
+    #[test]
+    fn captures_only_at_gated_levels() {
+        let full = vec!["full".to_string()];
+        let rendered = backtrace_of(
+            Placeholder::new(PlaceholderType::Backtrace, HashMap::new(), full.clone()),
+            || {
+                info!("info");
+                warn!("warn");
+                error!("error");
+            },
+        );
+        assert_eq!(rendered[..2], ["", ""]);
+        assert!(rendered[2].contains("captures_only_at_gated_levels"));
+
+        let warn_gate = HashMap::from([
+            (
+                "level".to_string(),
+                PlaceholderValue::String("warn".to_string()),
+            ),
+            ("limit".to_string(), PlaceholderValue::Integer(2)),
+        ]);
+        let rendered = backtrace_of(
+            Placeholder::new(PlaceholderType::Backtrace, warn_gate, full),
+            || {
+                info!("info");
+                warn!("warn");
+            },
+        );
+        assert_eq!(rendered[0], "");
+        let frames = rendered[1]
+            .lines()
+            .filter(|i| !i.trim_start().starts_with("at "))
+            .count();
+        assert_eq!(frames, 2);
+    }
+
+    #[test]
+    fn hides_logging_frames() {
+        assert!(is_internal("tracing_core::dispatcher::get_default"));
+        assert!(is_internal(
+            "<tracing_subscriber::layered::Layered<L,S> as Subscriber>::event"
+        ));
+        assert!(is_internal("std::rt::lang_start"));
+        assert!(!is_internal("my_app::handlers::checkout"));
+    }
+}
@@ -0,0 +1,116 @@
+//! Checks config files without starting the application, e.g. in CI.
+//!
+//! Loads each given file (or stdin), validates it, parses its patterns and prints the
+//! effective routing table, see [`ConfigFile`] for the format. Exits with a non-zero status
+//! if any config is invalid or a pattern references an unknown placeholder.
+//!
+//! ```text
+//! tracing-configurable-check [--quiet] [FILE]...
+//! ```
+
+use std::io::{self, Read};
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+use tracing_configurable::config::file::ConfigFile;
+use tracing_configurable::diagnostics::{self, Diagnostic};
+
+fn main() -> ExitCode {
+    let mut quiet = false;
+    let mut files = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "-q" | "--quiet" => quiet = true,
+            "-h" | "--help" => {
+                println!("usage: tracing-configurable-check [--quiet] [FILE]...");
+                return ExitCode::SUCCESS;
+            }
+            _ => files.push(arg),
+        }
+    }
+
+    let sources = if files.is_empty() {
+        let mut content = String::new();
+        if let Err(error) = io::stdin().read_to_string(&mut content) {
+            eprintln!("<stdin>: {}", error);
+            return ExitCode::FAILURE;
+        }
+
+        vec![("<stdin>".to_string(), Ok(content))]
+    } else {
+        files
+            .into_iter()
+            .map(|path| {
+                let content = std::fs::read_to_string(&path);
+                (path, content)
+            })
+            .collect()
+    };
+
+    let mut failed = false;
+    for (source, content) in sources {
+        let result = match content {
+            Ok(content) => check(&content),
+            Err(error) => Err(vec![error.to_string()]),
+        };
+
+        match result {
+            Ok(_) if quiet => {}
+            Ok(table) => print!("# {}\n{}", source, table),
+            Err(problems) => {
+                failed = true;
+                for problem in problems {
+                    eprintln!("{}: {}", source, problem);
+                }
+            }
+        }
+    }
+
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Checks a config, returning its routing table or the problems found.
+fn check(content: &str) -> Result<String, Vec<String>> {
+    let unknown = Arc::new(Mutex::new(Vec::new()));
+    diagnostics::set_handler({
+        let unknown = unknown.clone();
+        move |diagnostic| {
+            if let Diagnostic::UnknownPlaceholder { .. } = diagnostic {
+                unknown.lock().unwrap().push(diagnostic.to_string());
+            }
+        }
+    });
+
+    let result = ConfigFile::parse(content);
+    diagnostics::reset_handler();
+
+    let mut problems = std::mem::take(&mut *unknown.lock().unwrap());
+    match result {
+        Ok(config) if problems.is_empty() => Ok(config.routing_table()),
+        Ok(_) => Err(problems),
+        Err(error) => {
+            problems.push(error.to_string());
+            Err(problems)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::check;
+
+    #[test]
+    fn checks_configs() {
+        let config = "[appender console]\nkind = stdout\npattern = $level: $message\n\n[routes]\nmy_app=info -> console\n";
+        assert_eq!(check(config).unwrap(), "my_app  INFO   console (stdout)\n");
+
+        let problems = check(&config.replace("$level", "$levle")).unwrap_err();
+        assert_eq!(problems, ["unknown placeholder type `levle`"]);
+
+        let problems = check(&config.replace("console\n", "file\n")).unwrap_err();
+        assert_eq!(problems, ["line 6: unknown appender `file`"]);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::clock::Clock;
+use crate::config::matcher::TargetMatcher;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::Level;
+
+#[derive(Clone)]
+struct Boost {
+    target: String,
+    level: Level,
+    // as `Clock::elapsed`
+    until: Duration,
+}
+
+pub(crate) struct Boosts {
+    // number of boosts, lets events skip the lock while nothing is boosted
+    count: AtomicUsize,
+    state: RwLock<State>,
+    clock: Arc<dyn Clock>,
+}
+
+#[derive(Default)]
+struct State {
+    boosts: Vec<Boost>,
+    matcher: TargetMatcher<(Level, Duration)>,
+    // when the first of the boosts expires
+    next_expiry: Duration,
+}
+
+impl Boosts {
+    pub(crate) fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            count: AtomicUsize::new(0),
+            state: Default::default(),
+            clock,
+        }
+    }
+
+    /// Whether an active boost covering `target` lets `level` through.
+    pub(crate) fn enabled(&self, level: &Level, target: &str) -> bool {
+        if self.count.load(Ordering::Relaxed) == 0 {
+            return false;
+        }
+
+        let now = self.clock.elapsed();
+        if self.state.read().unwrap().next_expiry <= now {
+            // once the last boost expired events skip the lock again
+            self.update(|_| {});
+        }
+
+        let state = self.state.read().unwrap();
+        state
+            .matcher
+            .get(target)
+            .is_some_and(|(boost, until)| level <= boost && now < *until)
+    }
+
+    fn update<F: FnOnce(&mut Vec<Boost>)>(&self, f: F) {
+        let mut state = self.state.write().unwrap();
+
+        let now = self.clock.elapsed();
+        state.boosts.retain(|i| i.until > now);
+        f(&mut state.boosts);
+
+        // the matcher finds the most specific boost, so each one takes the level of the most
+        // verbose boost covering its target
+        state.matcher = state
+            .boosts
+            .iter()
+            .map(|i| {
+                let widest = state
+                    .boosts
+                    .iter()
+                    .filter(|j| covers(&j.target, &i.target))
+                    .max_by_key(|j| j.level)
+                    .unwrap_or(i);
+                (i.target.clone(), (widest.level, widest.until))
+            })
+            .collect();
+        state.next_expiry = state
+            .boosts
+            .iter()
+            .map(|i| i.until)
+            .min()
+            .unwrap_or(Duration::MAX);
+        self.count.store(state.boosts.len(), Ordering::Relaxed);
+    }
+}
+
+/// Whether a boost of `parent` applies to `target`.
+fn covers(parent: &str, target: &str) -> bool {
+    let mut matcher = TargetMatcher::new();
+    matcher.insert(parent, ());
+    matcher.get(target).is_some()
+}
+
+/// Temporarily raises verbosity for selected targets, e.g. from an admin endpoint.
+///
+/// Boosts only widen what [`LayerConfig::enabled`](crate::config::LayerConfig::enabled)
+/// lets through; events still go to the appenders the config returns for them.
+#[derive(Clone)]
+pub struct BoostHandle {
+    boosts: Arc<Boosts>,
+}
+
+impl BoostHandle {
+    pub(crate) fn new(boosts: Arc<Boosts>) -> Self {
+        Self { boosts }
+    }
+
+    /// Enables events up to `level` for `target` (and its children) for `duration`,
+    /// replacing an earlier boost of the same target. Where boosts overlap, the most verbose
+    /// one applies.
+    pub fn boost<S: AsRef<str>>(&self, target: S, level: Level, duration: Duration) {
+        let target = target.as_ref().to_string();
+        let until = self.boosts.clock.elapsed().saturating_add(duration);
+
+        self.boosts.update(|boosts| {
+            boosts.retain(|i| i.target != target);
+            boosts.push(Boost {
+                target,
+                level,
+                until,
+            });
+        });
+    }
+
+    /// Ends the boost of `target` before it expires.
+    pub fn cancel<S: AsRef<str>>(&self, target: S) {
+        self.boosts
+            .update(|boosts| boosts.retain(|i| i.target != target.as_ref()));
+    }
+
+    /// Active boosts as `(target, level, remaining)`.
+    pub fn active(&self) -> Vec<(String, Level, Duration)> {
+        let now = self.boosts.clock.elapsed();
+        let state = self.boosts.state.read().unwrap();
+
+        state
+            .boosts
+            .iter()
+            .filter(|i| i.until > now)
+            .map(|i| (i.target.clone(), i.level, i.until - now))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::boost::{BoostHandle, Boosts};
+    use crate::clock::{self, TestClock};
+    use chrono::{Local, TimeZone};
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tracing::Level;
+
+    #[test]
+    fn boost_expires() {
+        let boosts = Arc::new(Boosts::new(clock::system()));
+        let handle = BoostHandle::new(boosts.clone());
+
+        handle.boost("my_app::payments", Level::DEBUG, Duration::from_secs(60));
+        handle.boost("my_app::db", Level::TRACE, Duration::ZERO);
+
+        assert!(boosts.enabled(&Level::DEBUG, "my_app::payments::card"));
+        assert!(!boosts.enabled(&Level::TRACE, "my_app::payments"));
+        assert!(!boosts.enabled(&Level::DEBUG, "my_app::http"));
+        assert!(!boosts.enabled(&Level::TRACE, "my_app::db"));
+        assert_eq!(handle.active().len(), 1);
+
+        handle.cancel("my_app::payments");
+        assert!(!boosts.enabled(&Level::DEBUG, "my_app::payments"));
+    }
+
+    #[test]
+    fn overlapping_boosts_take_most_verbose_level() {
+        let boosts = Arc::new(Boosts::new(clock::system()));
+        let handle = BoostHandle::new(boosts.clone());
+
+        handle.boost("a", Level::TRACE, Duration::from_secs(60));
+        handle.boost("a::b", Level::INFO, Duration::from_secs(60));
+        handle.boost("c", Level::INFO, Duration::from_secs(60));
+        handle.boost("c::d", Level::DEBUG, Duration::from_secs(60));
+
+        assert!(boosts.enabled(&Level::TRACE, "a::b::c"));
+        assert!(boosts.enabled(&Level::DEBUG, "c::d"));
+        assert!(!boosts.enabled(&Level::DEBUG, "c::e"));
+
+        handle.cancel("a");
+        assert!(!boosts.enabled(&Level::DEBUG, "a::b"));
+        assert!(boosts.enabled(&Level::INFO, "a::b"));
+    }
+
+    #[test]
+    fn expired_boosts_are_pruned() {
+        let clock = TestClock::new(Local.with_ymd_and_hms(2024, 3, 1, 8, 30, 0).unwrap());
+        let boosts = Arc::new(Boosts::new(Arc::new(clock.clone())));
+        let handle = BoostHandle::new(boosts.clone());
+
+        handle.boost("my_app::payments", Level::DEBUG, Duration::from_secs(60));
+        handle.boost("my_app::db", Level::TRACE, Duration::from_secs(120));
+        assert_eq!(boosts.count.load(Ordering::Relaxed), 2);
+
+        clock.advance(Duration::from_secs(90));
+        assert!(!boosts.enabled(&Level::DEBUG, "my_app::payments"));
+        assert!(boosts.enabled(&Level::TRACE, "my_app::db"));
+        assert_eq!(boosts.count.load(Ordering::Relaxed), 1);
+        assert_eq!(handle.active()[0].2, Duration::from_secs(30));
+
+        clock.advance(Duration::from_secs(30));
+        assert!(!boosts.enabled(&Level::TRACE, "my_app::db"));
+        assert_eq!(boosts.count.load(Ordering::Relaxed), 0);
+        assert!(handle.active().is_empty());
+    }
+}
@@ -0,0 +1,144 @@
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+static INITIAL_CAPACITY: AtomicUsize = AtomicUsize::new(256);
+static SHRINK_THRESHOLD: AtomicUsize = AtomicUsize::new(64 * 1024);
+
+static BUFFERS: AtomicUsize = AtomicUsize::new(0);
+static CAPACITY: AtomicUsize = AtomicUsize::new(0);
+static SHRINKS: AtomicU64 = AtomicU64::new(0);
+static MAX_LEN: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static BUF: RefCell<Buffer> = RefCell::new(Buffer::new());
+}
+
+/// Sizing of the per-thread buffer lines are rendered into.
+#[derive(Debug, Clone, Copy)]
+pub struct BufferPolicy {
+    /// Capacity a thread's buffer starts with, and is shrunk back to.
+    pub initial_capacity: usize,
+    /// Once a render leaves the buffer with more capacity than this, it's shrunk.
+    pub shrink_threshold: usize,
+}
+
+impl Default for BufferPolicy {
+    fn default() -> Self {
+        Self {
+            initial_capacity: 256,
+            shrink_threshold: 64 * 1024,
+        }
+    }
+}
+
+/// Applies to buffers created from now on; existing buffers pick up the new threshold
+/// on their next render.
+pub fn set_policy(policy: BufferPolicy) {
+    INITIAL_CAPACITY.store(policy.initial_capacity, Ordering::Relaxed);
+    SHRINK_THRESHOLD.store(
+        policy.shrink_threshold.max(policy.initial_capacity),
+        Ordering::Relaxed,
+    );
+}
+
+pub fn policy() -> BufferPolicy {
+    BufferPolicy {
+        initial_capacity: INITIAL_CAPACITY.load(Ordering::Relaxed),
+        shrink_threshold: SHRINK_THRESHOLD.load(Ordering::Relaxed),
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BufferStats {
+    /// Threads currently holding a render buffer.
+    pub buffers: usize,
+    /// Bytes reserved by all live buffers.
+    pub capacity: usize,
+    /// Times a buffer was shrunk back after an oversized render.
+    pub shrinks: u64,
+    /// Longest line rendered so far.
+    pub max_len: usize,
+}
+
+pub fn stats() -> BufferStats {
+    BufferStats {
+        buffers: BUFFERS.load(Ordering::Relaxed),
+        capacity: CAPACITY.load(Ordering::Relaxed),
+        shrinks: SHRINKS.load(Ordering::Relaxed),
+        max_len: MAX_LEN.load(Ordering::Relaxed),
+    }
+}
+
+struct Buffer(String);
+
+impl Buffer {
+    fn new() -> Self {
+        let buf = String::with_capacity(INITIAL_CAPACITY.load(Ordering::Relaxed));
+
+        BUFFERS.fetch_add(1, Ordering::Relaxed);
+        CAPACITY.fetch_add(buf.capacity(), Ordering::Relaxed);
+
+        Self(buf)
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        BUFFERS.fetch_sub(1, Ordering::Relaxed);
+        CAPACITY.fetch_sub(self.0.capacity(), Ordering::Relaxed);
+    }
+}
+
+/// Runs `f` with this thread's (empty) render buffer, applying the shrink policy afterwards.
+pub(crate) fn with_buffer<R, F: FnOnce(&mut String) -> R>(f: F) -> R {
+    BUF.with(|buf| {
+        let buf = &mut buf.borrow_mut().0;
+        let before = buf.capacity();
+
+        let ret = f(buf);
+
+        MAX_LEN.fetch_max(buf.len(), Ordering::Relaxed);
+        buf.clear();
+
+        if buf.capacity() > SHRINK_THRESHOLD.load(Ordering::Relaxed) {
+            buf.shrink_to(INITIAL_CAPACITY.load(Ordering::Relaxed));
+            SHRINKS.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let after = buf.capacity();
+        if after > before {
+            CAPACITY.fetch_add(after - before, Ordering::Relaxed);
+        } else {
+            CAPACITY.fetch_sub(before - after, Ordering::Relaxed);
+        }
+
+        ret
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::buffer::{policy, stats, with_buffer};
+
+    #[test]
+    fn shrinks_after_oversized_render() {
+        // a thread of its own for a fresh buffer; the policy is process-wide, keep the default
+        let policy = policy();
+
+        std::thread::spawn(move || {
+            let moderate = policy.shrink_threshold / 2;
+            with_buffer(|buf| buf.push_str(&"x".repeat(moderate)));
+            assert!(with_buffer(|buf| buf.capacity()) >= moderate);
+
+            let shrinks = stats().shrinks;
+            with_buffer(|buf| buf.push_str(&"x".repeat(policy.shrink_threshold * 2)));
+
+            assert!(with_buffer(|buf| buf.capacity()) <= policy.shrink_threshold);
+            assert!(stats().shrinks > shrinks);
+            assert!(stats().max_len >= policy.shrink_threshold * 2);
+        })
+        .join()
+        .unwrap();
+    }
+}
@@ -0,0 +1,167 @@
+use chrono::{DateTime, Local};
+use once_cell::sync::Lazy;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Time source for `$datetime`, `$elapsed` and anything else that needs the current time.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Local>;
+
+    /// Time passed since the clock was created.
+    fn elapsed(&self) -> Duration;
+}
+
+static SYSTEM: Lazy<Arc<SystemClock>> = Lazy::new(|| Arc::new(SystemClock::new()));
+
+/// The process-wide system clock, used by the layer and appenders unless they're given
+/// another one. Its `elapsed` counts from the first call.
+pub fn system() -> Arc<dyn Clock> {
+    SYSTEM.clone()
+}
+
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> DateTime<Local> {
+        (**self).now()
+    }
+
+    fn elapsed(&self) -> Duration {
+        (**self).elapsed()
+    }
+}
+
+/// Clock that only moves when told to, for deterministic output in tests.
+///
+/// Clones share the same time, so the test can keep one and hand the other to the layer.
+#[derive(Clone)]
+pub struct TestClock {
+    inner: Arc<Mutex<TestClockState>>,
+}
+
+struct TestClockState {
+    start: DateTime<Local>,
+    now: DateTime<Local>,
+}
+
+impl TestClock {
+    pub fn new(start: DateTime<Local>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(TestClockState { start, now: start })),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        let mut state = self.inner.lock().unwrap();
+        state.now += chrono::Duration::from_std(duration).expect("duration out of range");
+    }
+
+    /// Moves the clock to `now`; `elapsed` never goes below zero.
+    pub fn set(&self, now: DateTime<Local>) {
+        self.inner.lock().unwrap().now = now;
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> DateTime<Local> {
+        self.inner.lock().unwrap().now
+    }
+
+    fn elapsed(&self) -> Duration {
+        let state = self.inner.lock().unwrap();
+        (state.now - state.start).to_std().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::clock::{Clock, TestClock};
+    use crate::pattern::{Pattern, PatternItem, Placeholder, PlaceholderType, PlaceholderValue};
+    use crate::testing::{CaptureAppender, TestConfig};
+    use crate::ConfigurableLayer;
+    use chrono::{Local, TimeZone};
+    use std::collections::HashMap;
+    use std::time::Duration;
+    use tracing::info;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::registry;
+
+    #[test]
+    fn test_clock_moves_when_told() {
+        let start = Local.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let clock = TestClock::new(start);
+        let shared = clock.clone();
+
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(
+            shared.now(),
+            Local.with_ymd_and_hms(2024, 3, 1, 12, 1, 30).unwrap()
+        );
+        assert_eq!(shared.elapsed(), Duration::from_secs(90));
+
+        clock.set(Local.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap());
+        assert_eq!(shared.elapsed(), Duration::ZERO);
+    }
+
+    #[test]
+    fn layer_renders_with_its_clock() {
+        let mut properties = HashMap::new();
+        properties.insert(
+            "fmt".to_string(),
+            PlaceholderValue::String("%H:%M:%S".to_string()),
+        );
+        let pattern = Pattern::new(vec![
+            PatternItem::Placeholder(Placeholder::new(
+                PlaceholderType::DateTime,
+                properties,
+                vec![],
+            )),
+            PatternItem::Text(" ".to_string()),
+            PatternItem::Placeholder(Placeholder::new(
+                PlaceholderType::Message,
+                HashMap::new(),
+                vec![],
+            )),
+        ]);
+
+        let clock = TestClock::new(Local.with_ymd_and_hms(2024, 3, 1, 8, 30, 0).unwrap());
+        let capture = CaptureAppender::with_pattern(pattern);
+        let layer =
+            ConfigurableLayer::new(TestConfig::new(capture.clone())).with_clock(clock.clone());
+
+        tracing::subscriber::with_default(registry().with(layer), || {
+            info!("first");
+            clock.advance(Duration::from_secs(5));
+            info!("second");
+        });
+
+        let rendered: Vec<String> = capture.events().into_iter().map(|i| i.rendered).collect();
+        assert_eq!(rendered, ["08:30:00 first", "08:30:05 second"]);
+    }
+}
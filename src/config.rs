@@ -1,7 +1,148 @@
 use crate::appender::Appender;
+use crate::diagnostics::{self, Diagnostic};
+use crate::fields::{EventValue, FieldsVisitor};
+use std::time::Instant;
 use tracing::Level;
 
+#[cfg(feature = "parse")]
+pub mod file;
+pub mod filter;
+pub mod matcher;
+pub mod remap;
+
 pub trait LayerConfig: Send + Sync {
     fn enabled(&self, level: &Level, module: &str) -> bool;
     fn get_appenders(&self, level: &Level, module: &str) -> Vec<Box<dyn Appender>>;
+
+    /// Whether routing depends on the spans events are recorded in. When it does,
+    /// [`get_span_appenders`](Self::get_span_appenders) is called instead of `get_appenders`.
+    fn routes_by_span(&self) -> bool {
+        false
+    }
+
+    /// Appenders for an event recorded inside `spans`, e.g. events inside any span with
+    /// `audit = true` go to the audit appender as well.
+    ///
+    /// Events kept for [`with_dump_on_error`](crate::ConfigurableLayer::with_dump_on_error)
+    /// are routed with `get_appenders`.
+    fn get_span_appenders(
+        &self,
+        level: &Level,
+        module: &str,
+        spans: &SpanScope<'_>,
+    ) -> Vec<Box<dyn Appender>> {
+        let _ = spans;
+        self.get_appenders(level, module)
+    }
+
+    /// Level the event is filtered, routed and rendered with, e.g. to demote a noisy
+    /// dependency's WARN to DEBUG. See [`LevelRemap`](remap::LevelRemap).
+    fn remap_level(&self, level: &Level, module: &str) -> Level {
+        let _ = module;
+        *level
+    }
+
+    /// Called once the layer stopped accepting events: drain, flush and close appenders,
+    /// finishing before `deadline` where possible.
+    ///
+    /// Closes the appenders [`get_appenders`](Self::get_appenders) returns for the root
+    /// target at every level. Configs routing targets to other appenders have to close
+    /// those themselves.
+    fn shutdown(&self, deadline: Instant) {
+        let levels = [
+            Level::ERROR,
+            Level::WARN,
+            Level::INFO,
+            Level::DEBUG,
+            Level::TRACE,
+        ];
+
+        for level in levels {
+            for appender in self.get_appenders(&level, "") {
+                if let Err(error) = appender.close(deadline) {
+                    diagnostics::report(Diagnostic::FlushError { error: &error });
+                }
+            }
+        }
+    }
+}
+
+/// The spans an event was recorded in, innermost first.
+pub struct SpanScope<'a> {
+    spans: Vec<(&'static str, Option<&'a FieldsVisitor>)>,
+}
+
+impl<'a> SpanScope<'a> {
+    pub(crate) fn new(spans: Vec<(&'static str, Option<&'a FieldsVisitor>)>) -> Self {
+        Self { spans }
+    }
+
+    /// Names and fields of the spans, innermost first.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, Option<&'a FieldsVisitor>)> + '_ {
+        self.spans.iter().copied()
+    }
+
+    /// Whether the event is inside a span called `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.spans.iter().any(|(i, _)| *i == name)
+    }
+
+    /// Value of the field `name` from the innermost span recording it.
+    pub fn field(&self, name: &str) -> Option<&'a EventValue> {
+        self.spans.iter().find_map(|&(_, fields)| {
+            fields?
+                .values()
+                .find(|(key, _)| *key == name)
+                .and_then(|(_, values)| values.first())
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::appender::Appender;
+    use crate::testing::{CaptureAppender, TestConfig};
+    use crate::ConfigurableLayer;
+    use tracing::{info, info_span};
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::registry;
+
+    #[test]
+    fn routes_by_span_fields() {
+        let main = CaptureAppender::new();
+        let audit = CaptureAppender::new();
+        let config = TestConfig::new(main.clone()).with_span_route({
+            let audit = audit.clone();
+            move |spans| match spans.field("audit") {
+                Some(i) if i.to_string() == "true" => {
+                    vec![Box::new(audit.clone()) as Box<dyn Appender>]
+                }
+                _ => vec![],
+            }
+        });
+        let subscriber = registry().with(ConfigurableLayer::new(config));
+
+        tracing::subscriber::with_default(subscriber, || {
+            info!("outside");
+
+            let request = info_span!("request", audit = true);
+            {
+                let _request = request.enter();
+                let _query = info_span!("query").entered();
+                info!("inside");
+            }
+
+            // re-entered spans keep their fields
+            let _request = request.enter();
+            info!("again");
+        });
+
+        assert_eq!(main.events().len(), 3);
+        let messages: Vec<String> = audit.events().into_iter().map(|i| i.message).collect();
+        assert_eq!(messages, ["inside", "again"]);
+    }
 }
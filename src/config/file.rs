@@ -0,0 +1,451 @@
+//! Configs read from a file, so routing can change without rebuilding the application.
+//!
+//! ```text
+//! [appender console]
+//! kind = stdout
+//! pattern = $level $target: $message
+//!
+//! [appender app]
+//! kind = file
+//! path = /var/log/app.log
+//! pattern = $datetime $level $target: $message
+//!
+//! [routes]
+//! my_app=debug -> app, console
+//! hyper=warn -> console
+//! !my_app::metrics
+//! ```
+//!
+//! Appenders have a `kind` of `stdout`, `stderr`, `file` or `json` (newline-delimited JSON).
+//! Files take a `path`, all but `json` a `pattern`. Routes use the rule syntax of
+//! [`TargetFilter`](crate::config::filter::TargetFilter), followed by the appenders the
+//! events go to; the most specific rule for a target decides. Blank lines and lines starting
+//! with `#` are skipped.
+
+use crate::appender::file::FileAppender;
+use crate::appender::json_file::JsonFileAppender;
+use crate::appender::writer::WriterAppender;
+use crate::appender::Appender;
+use crate::config::matcher::TargetMatcher;
+use crate::config::LayerConfig;
+use crate::diagnostics::{self, Diagnostic};
+use crate::pattern::Pattern;
+use anyhow::{anyhow, Context};
+use std::fmt::Write;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::Level;
+
+enum AppenderKind {
+    Stdout,
+    Stderr,
+    File(PathBuf),
+    Json(PathBuf),
+}
+
+/// An appender section as written, with the line it starts at and its keys by line.
+struct AppenderSection {
+    line_no: usize,
+    name: String,
+    keys: Vec<(usize, String, String)>,
+}
+
+struct AppenderSpec {
+    name: String,
+    kind: AppenderKind,
+    pattern: Option<Pattern>,
+}
+
+impl AppenderSpec {
+    fn describe(&self) -> String {
+        match &self.kind {
+            AppenderKind::Stdout => format!("{} (stdout)", self.name),
+            AppenderKind::Stderr => format!("{} (stderr)", self.name),
+            AppenderKind::File(path) => format!("{} (file {})", self.name, path.display()),
+            AppenderKind::Json(path) => format!("{} (json {})", self.name, path.display()),
+        }
+    }
+
+    fn build(self) -> io::Result<Arc<dyn Appender + Send + Sync>> {
+        // only `json` has no pattern, which parsing made sure of
+        let pattern = self.pattern.unwrap_or_else(|| Pattern::new(vec![]));
+
+        Ok(match self.kind {
+            AppenderKind::Stdout => Arc::new(WriterAppender::new(pattern, io::stdout())),
+            AppenderKind::Stderr => Arc::new(WriterAppender::new(pattern, io::stderr())),
+            AppenderKind::File(path) => Arc::new(FileAppender::new(pattern, path)?),
+            AppenderKind::Json(path) => Arc::new(JsonFileAppender::new(path)?),
+        })
+    }
+}
+
+struct Route {
+    target: String,
+    // `None` for exclusions
+    rule: Option<(Level, Vec<usize>)>,
+}
+
+/// A parsed and validated config file, see the [module documentation](self) for the format.
+///
+/// Parsing doesn't create any appenders, [`build`](Self::build) does.
+pub struct ConfigFile {
+    appenders: Vec<AppenderSpec>,
+    routes: Vec<Route>,
+}
+
+impl ConfigFile {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, anyhow::Error> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("can't read {}", path.display()))?;
+
+        Self::parse(&content)
+    }
+
+    /// Parses a config, failing with the line of the first problem found. Patterns are
+    /// parsed too; unknown placeholders are reported as
+    /// [`Diagnostic::UnknownPlaceholder`](crate::diagnostics::Diagnostic::UnknownPlaceholder).
+    pub fn parse(content: &str) -> Result<Self, anyhow::Error> {
+        enum Section {
+            None,
+            Appender,
+            Routes,
+        }
+
+        let mut section = Section::None;
+        let mut sections: Vec<AppenderSection> = Vec::new();
+        let mut routes = Vec::new();
+
+        for (idx, line) in content.lines().enumerate() {
+            let line_no = idx + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('[') {
+                let header = header
+                    .strip_suffix(']')
+                    .ok_or_else(|| anyhow!("line {}: unclosed section header", line_no))?;
+
+                section = match header.split_whitespace().collect::<Vec<_>>()[..] {
+                    ["appender", name] => {
+                        if sections.iter().any(|i| i.name == name) {
+                            return Err(anyhow!(
+                                "line {}: appender `{}` is defined twice",
+                                line_no,
+                                name
+                            ));
+                        }
+
+                        sections.push(AppenderSection {
+                            line_no,
+                            name: name.to_string(),
+                            keys: Vec::new(),
+                        });
+                        Section::Appender
+                    }
+                    ["routes"] => Section::Routes,
+                    _ => return Err(anyhow!("line {}: unknown section `{}`", line_no, header)),
+                };
+                continue;
+            }
+
+            match section {
+                Section::None => {
+                    return Err(anyhow!("line {}: expected a section header", line_no))
+                }
+                Section::Appender => {
+                    let (key, value) = line
+                        .split_once('=')
+                        .ok_or_else(|| anyhow!("line {}: expected `key = value`", line_no))?;
+                    let section = sections.last_mut().unwrap();
+                    section
+                        .keys
+                        .push((line_no, key.trim().to_string(), value.trim().to_string()));
+                }
+                Section::Routes => routes.push((line_no, line)),
+            }
+        }
+
+        let appenders = sections
+            .into_iter()
+            .map(parse_appender)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let routes = routes
+            .into_iter()
+            .map(|(line_no, line)| {
+                parse_route(line, &appenders).map_err(|e| anyhow!("line {}: {}", line_no, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { appenders, routes })
+    }
+
+    /// The routes with the level they're enabled from and where events go, sorted by
+    /// target, one per line.
+    pub fn routing_table(&self) -> String {
+        let mut routes: Vec<(String, String)> = self
+            .routes
+            .iter()
+            .map(|route| {
+                let target = match route.target.as_str() {
+                    "" => "*",
+                    target => target,
+                };
+
+                match &route.rule {
+                    Some((level, appenders)) => {
+                        let appenders: Vec<String> = appenders
+                            .iter()
+                            .map(|i| self.appenders[*i].describe())
+                            .collect();
+                        let to = format!("{:<5}  {}", level.as_str(), appenders.join(", "));
+                        (target.to_string(), to)
+                    }
+                    None => (format!("!{}", target), "excluded".to_string()),
+                }
+            })
+            .collect();
+        routes.sort_by(|a, b| a.0.trim_start_matches('!').cmp(b.0.trim_start_matches('!')));
+
+        let width = routes.iter().map(|i| i.0.len()).max().unwrap_or_default();
+        routes
+            .iter()
+            .fold(String::new(), |mut table, (target, to)| {
+                let _ = writeln!(table, "{:width$}  {}", target, to, width = width);
+                table
+            })
+    }
+
+    /// Creates the appenders, opening their files.
+    pub fn build(self) -> io::Result<FileConfig> {
+        let appenders = self
+            .appenders
+            .into_iter()
+            .map(AppenderSpec::build)
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let mut routes = TargetMatcher::new();
+        for route in self.routes {
+            match route.rule {
+                Some((level, indexes)) => {
+                    let selected = indexes.iter().map(|i| appenders[*i].clone()).collect();
+                    routes.insert(route.target, (level, selected));
+                }
+                None => routes.exclude(route.target),
+            }
+        }
+
+        Ok(FileConfig { appenders, routes })
+    }
+}
+
+fn parse_appender(section: AppenderSection) -> Result<AppenderSpec, anyhow::Error> {
+    let AppenderSection {
+        line_no,
+        name,
+        keys,
+    } = section;
+    let mut kind = None;
+    let mut path = None;
+    let mut pattern = None;
+
+    for (line_no, key, value) in keys {
+        match key.as_str() {
+            "kind" => kind = Some(value),
+            "path" => path = Some(PathBuf::from(value)),
+            "pattern" => {
+                let parsed = Pattern::try_parse(&value)
+                    .map_err(|e| anyhow!("line {}: invalid pattern: {}", line_no, e))?;
+                pattern = Some(parsed);
+            }
+            _ => return Err(anyhow!("line {}: unknown key `{}`", line_no, key)),
+        }
+    }
+
+    let error = |message: &str| anyhow!("line {}: appender `{}` {}", line_no, name, message);
+
+    let kind = match (kind.as_deref(), path) {
+        (None, _) => return Err(error("has no kind")),
+        (Some("stdout"), None) => AppenderKind::Stdout,
+        (Some("stderr"), None) => AppenderKind::Stderr,
+        (Some("file"), Some(path)) => AppenderKind::File(path),
+        (Some("json"), Some(path)) => AppenderKind::Json(path),
+        (Some("stdout" | "stderr"), Some(_)) => return Err(error("can't have a path")),
+        (Some("file" | "json"), None) => return Err(error("has no path")),
+        (Some(kind), _) => return Err(error(&format!("has unknown kind `{}`", kind))),
+    };
+
+    match (&kind, &pattern) {
+        (AppenderKind::Json(_), Some(_)) => return Err(error("can't have a pattern")),
+        (AppenderKind::Json(_), None) | (_, Some(_)) => {}
+        (_, None) => return Err(error("has no pattern")),
+    }
+
+    Ok(AppenderSpec {
+        name,
+        kind,
+        pattern,
+    })
+}
+
+fn parse_route(line: &str, appenders: &[AppenderSpec]) -> Result<Route, anyhow::Error> {
+    if let Some(target) = line.strip_prefix('!') {
+        if target.contains(['=', '>']) {
+            return Err(anyhow!(
+                "exclusion `{}` can't have a level or appenders",
+                line
+            ));
+        }
+
+        return Ok(Route {
+            target: target.trim().to_string(),
+            rule: None,
+        });
+    }
+
+    let (rule, names) = line
+        .split_once("->")
+        .ok_or_else(|| anyhow!("route `{}` has no appenders", line))?;
+
+    let (target, level) = match rule.split_once('=') {
+        Some((target, level)) => {
+            let level = level
+                .trim()
+                .parse::<Level>()
+                .map_err(|_| anyhow!("invalid level in `{}`", rule.trim()))?;
+            (target, level)
+        }
+        None => (rule, Level::TRACE),
+    };
+
+    let indexes = names
+        .split(',')
+        .map(str::trim)
+        .filter(|i| !i.is_empty())
+        .map(|name| {
+            appenders
+                .iter()
+                .position(|i| i.name == name)
+                .ok_or_else(|| anyhow!("unknown appender `{}`", name))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if indexes.is_empty() {
+        return Err(anyhow!("route `{}` has no appenders", line));
+    }
+
+    Ok(Route {
+        target: target.trim().to_string(),
+        rule: Some((level, indexes)),
+    })
+}
+
+/// A [`LayerConfig`] built from a [`ConfigFile`].
+pub struct FileConfig {
+    appenders: Vec<Arc<dyn Appender + Send + Sync>>,
+    routes: TargetMatcher<(Level, Vec<Arc<dyn Appender + Send + Sync>>)>,
+}
+
+impl LayerConfig for FileConfig {
+    fn enabled(&self, level: &Level, module: &str) -> bool {
+        self.routes.get(module).is_some_and(|(max, _)| level <= max)
+    }
+
+    fn get_appenders(&self, level: &Level, module: &str) -> Vec<Box<dyn Appender>> {
+        match self.routes.get(module) {
+            Some((max, appenders)) if level <= max => appenders
+                .iter()
+                .map(|i| Box::new(i.clone()) as Box<dyn Appender>)
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Closes every appender of the file, routed to or not.
+    fn shutdown(&self, deadline: Instant) {
+        for appender in &self.appenders {
+            if let Err(error) = appender.close(deadline) {
+                diagnostics::report(Diagnostic::FlushError { error: &error });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::file::ConfigFile;
+    use crate::config::LayerConfig;
+    use tracing::Level;
+
+    const CONFIG: &str = "
+        # development setup
+        [appender console]
+        kind = stdout
+        pattern = $level $target: $message
+
+        [appender app]
+        kind = json
+        path = app.json
+
+        [routes]
+        my_app=debug -> app, console
+        hyper=warn -> console
+        !my_app::metrics
+    ";
+
+    #[test]
+    fn parses_routes() {
+        let config = ConfigFile::parse(CONFIG).unwrap();
+        assert_eq!(
+            config.routing_table(),
+            "hyper             WARN   console (stdout)\n\
+             my_app            DEBUG  app (json app.json), console (stdout)\n\
+             !my_app::metrics  excluded\n"
+        );
+    }
+
+    #[test]
+    fn reports_invalid_configs() {
+        let error = |content: &str| ConfigFile::parse(content).err().unwrap().to_string();
+
+        assert_eq!(
+            error("[appender a]\nkind = file\npattern = $message"),
+            "line 1: appender `a` has no path"
+        );
+        assert_eq!(
+            error("[appender a]\nkind = syslog"),
+            "line 1: appender `a` has unknown kind `syslog`"
+        );
+        assert_eq!(
+            error("[appender a]\nkind = json\npath = a.json\n[routes]\nmy_app -> a, b"),
+            "line 5: unknown appender `b`"
+        );
+        assert_eq!(
+            error("[routes]\nmy_app=loud -> a"),
+            "line 2: invalid level in `my_app=loud`"
+        );
+        assert_eq!(error("kind = stdout"), "line 1: expected a section header");
+    }
+
+    #[test]
+    fn routes_built_config() {
+        let config = ConfigFile::parse(
+            "[appender err]\nkind = stderr\npattern = $message\n[routes]\n*=warn -> err\nnoisy=error -> err\n!quiet",
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+        assert!(config.enabled(&Level::WARN, "my_app"));
+        assert!(!config.enabled(&Level::INFO, "my_app"));
+        assert!(!config.enabled(&Level::WARN, "noisy::db"));
+        assert!(!config.enabled(&Level::ERROR, "quiet"));
+        assert_eq!(config.get_appenders(&Level::ERROR, "noisy").len(), 1);
+        assert!(config.get_appenders(&Level::INFO, "my_app").is_empty());
+    }
+}
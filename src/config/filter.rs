@@ -0,0 +1,92 @@
+use crate::config::matcher::TargetMatcher;
+use anyhow::anyhow;
+use tracing::Level;
+
+/// Target filter for [`LayerConfig::enabled`](crate::config::LayerConfig::enabled), built
+/// from rules such as `my_app::*=debug, hyper=warn, !my_app::metrics`.
+///
+/// Each rule is a target, optionally followed by `=level` (all levels when left out).
+/// A `!` in front of the target excludes it and the modules below it. The most specific
+/// rule for a target decides; an exclusion wins over a rule for the same target, and
+/// targets no rule covers are disabled.
+#[derive(Default)]
+pub struct TargetFilter {
+    rules: TargetMatcher<Level>,
+}
+
+impl TargetFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses comma-separated rules, see the type documentation for the syntax.
+    pub fn parse<S: AsRef<str>>(rules: S) -> Result<Self, anyhow::Error> {
+        let mut filter = Self::new();
+
+        for rule in rules.as_ref().split(',').map(str::trim) {
+            if rule.is_empty() {
+                continue;
+            }
+
+            if let Some(target) = rule.strip_prefix('!') {
+                if target.contains('=') {
+                    return Err(anyhow!("exclusion `{}` can't have a level", rule));
+                }
+
+                filter = filter.exclude(target);
+                continue;
+            }
+
+            filter = match rule.split_once('=') {
+                Some((target, level)) => {
+                    let level = level
+                        .trim()
+                        .parse::<Level>()
+                        .map_err(|_| anyhow!("invalid level in `{}`", rule))?;
+                    filter.include(target, level)
+                }
+                None => filter.include(rule, Level::TRACE),
+            };
+        }
+
+        Ok(filter)
+    }
+
+    /// Enables events of `target` (and the modules below it) at `level` or more severe.
+    pub fn include<S: AsRef<str>>(mut self, target: S, level: Level) -> Self {
+        self.rules.insert(target, level);
+        self
+    }
+
+    /// Disables `target` and the modules below it, unless a more specific rule enables them.
+    pub fn exclude<S: AsRef<str>>(mut self, target: S) -> Self {
+        self.rules.exclude(target);
+        self
+    }
+
+    pub fn enabled(&self, level: &Level, target: &str) -> bool {
+        self.rules.get(target).is_some_and(|max| level <= max)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::filter::TargetFilter;
+    use tracing::Level;
+
+    #[test]
+    fn excludes_targets() {
+        let filter =
+            TargetFilter::parse("my_app::*=debug, !my_app::metrics, my_app::metrics::http=warn")
+                .unwrap();
+
+        assert!(filter.enabled(&Level::DEBUG, "my_app::db"));
+        assert!(!filter.enabled(&Level::TRACE, "my_app::db"));
+        assert!(!filter.enabled(&Level::ERROR, "my_app::metrics::gauges"));
+        assert!(filter.enabled(&Level::WARN, "my_app::metrics::http"));
+        assert!(!filter.enabled(&Level::ERROR, "hyper"));
+
+        assert!(TargetFilter::parse("my_app=loud").is_err());
+        assert!(TargetFilter::parse("!my_app=info").is_err());
+    }
+}
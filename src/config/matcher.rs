@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+/// Target rules compiled into a trie over `::`-separated path segments.
+///
+/// A rule for `my_app::net` (or `my_app::net::*`) applies to that module and everything
+/// below it, but not to `my_app::network`; an empty rule (or `*`) applies to all targets.
+/// Lookups walk the target once and return the most specific rule.
+///
+/// An exclusion (see [`exclude`](Self::exclude)) is a rule too: when it's the most specific
+/// one for a target, nothing matches. It takes precedence over a rule for the same target.
+pub struct TargetMatcher<T> {
+    root: Node<T>,
+}
+
+struct Node<T> {
+    value: Option<T>,
+    excluded: bool,
+    children: HashMap<String, Node<T>>,
+}
+
+impl<T> Default for Node<T> {
+    fn default() -> Self {
+        Self {
+            value: None,
+            excluded: false,
+            children: HashMap::new(),
+        }
+    }
+}
+
+impl<T> TargetMatcher<T> {
+    pub fn new() -> Self {
+        Self {
+            root: Node::default(),
+        }
+    }
+
+    /// Adds a rule, replacing the value of an identical one.
+    pub fn insert<S: AsRef<str>>(&mut self, target: S, value: T) {
+        let mut node = &mut self.root;
+        for segment in segments(target.as_ref()) {
+            node = node.children.entry(segment.to_string()).or_default();
+        }
+
+        node.value = Some(value);
+    }
+
+    /// Adds an exclusion, e.g. `my_app::metrics` under a rule for `my_app`, so that module
+    /// and everything below it match no rule.
+    pub fn exclude<S: AsRef<str>>(&mut self, target: S) {
+        let mut node = &mut self.root;
+        for segment in segments(target.as_ref()) {
+            node = node.children.entry(segment.to_string()).or_default();
+        }
+
+        node.excluded = true;
+    }
+
+    /// Returns the value of the rule for exactly `target`, adding one with `default()` first
+    /// if there's none.
+    pub fn get_or_insert_with<S: AsRef<str>, F: FnOnce() -> T>(
+        &mut self,
+        target: S,
+        default: F,
+    ) -> &mut T {
+        let mut node = &mut self.root;
+        for segment in segments(target.as_ref()) {
+            node = node.children.entry(segment.to_string()).or_default();
+        }
+
+        node.value.get_or_insert_with(default)
+    }
+
+    /// Returns the value of the most specific rule matching `target`.
+    pub fn get(&self, target: &str) -> Option<&T> {
+        let mut node = &self.root;
+        let mut matched = node.value.as_ref().filter(|_| !node.excluded);
+
+        for segment in target.split("::") {
+            match node.children.get(segment) {
+                Some(child) => node = child,
+                None => break,
+            }
+
+            if node.excluded {
+                matched = None;
+            } else if let Some(value) = &node.value {
+                matched = Some(value);
+            }
+        }
+
+        matched
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.value.is_none() && !self.root.excluded && self.root.children.is_empty()
+    }
+}
+
+impl<T> Default for TargetMatcher<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: AsRef<str>, T> FromIterator<(S, T)> for TargetMatcher<T> {
+    fn from_iter<I: IntoIterator<Item = (S, T)>>(iter: I) -> Self {
+        let mut matcher = Self::new();
+        for (target, value) in iter {
+            matcher.insert(target, value);
+        }
+
+        matcher
+    }
+}
+
+fn segments(target: &str) -> impl Iterator<Item = &str> {
+    let target = target.trim();
+    let target = target
+        .strip_suffix("::*")
+        .or_else(|| target.strip_suffix('*').filter(|i| i.is_empty()))
+        .unwrap_or(target);
+
+    target.split("::").filter(|i| !i.is_empty())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::matcher::TargetMatcher;
+
+    #[test]
+    fn most_specific_rule_wins() {
+        let matcher: TargetMatcher<u8> =
+            [("*", 0), ("my_app", 1), ("my_app::net::*", 2), ("hyper", 3)]
+                .into_iter()
+                .collect();
+
+        assert_eq!(matcher.get("tokio::runtime"), Some(&0));
+        assert_eq!(matcher.get("my_app"), Some(&1));
+        assert_eq!(matcher.get("my_app::db"), Some(&1));
+        assert_eq!(matcher.get("my_app::net"), Some(&2));
+        assert_eq!(matcher.get("my_app::net::http"), Some(&2));
+        assert_eq!(matcher.get("my_app_cli"), Some(&0));
+        assert_eq!(matcher.get("hyper::client"), Some(&3));
+    }
+
+    #[test]
+    fn exclusions_cut_out_subtrees() {
+        let mut matcher: TargetMatcher<u8> = [("my_app", 1), ("my_app::metrics::http", 2)]
+            .into_iter()
+            .collect();
+        matcher.exclude("my_app::metrics");
+        matcher.exclude("my_app::db");
+        matcher.insert("my_app::db", 3);
+
+        assert_eq!(matcher.get("my_app::net"), Some(&1));
+        assert_eq!(matcher.get("my_app::metrics"), None);
+        assert_eq!(matcher.get("my_app::metrics::gauges"), None);
+        assert_eq!(matcher.get("my_app::metrics::http"), Some(&2));
+        assert_eq!(matcher.get("my_app::db"), None);
+    }
+}
@@ -0,0 +1,51 @@
+use crate::config::matcher::TargetMatcher;
+use tracing::Level;
+
+/// Level remapping rules for [`LayerConfig::remap_level`](crate::config::LayerConfig::remap_level).
+///
+/// Rules are looked up like other target rules: the most specific target with rules wins,
+/// and only its rules apply.
+#[derive(Default)]
+pub struct LevelRemap {
+    rules: TargetMatcher<Vec<(Level, Level)>>,
+}
+
+impl LevelRemap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Treats `from` events of `target` (and the modules below it) as `to` events.
+    pub fn with_rule<S: AsRef<str>>(mut self, target: S, from: Level, to: Level) -> Self {
+        let rules = self.rules.get_or_insert_with(target, Vec::new);
+        rules.retain(|(i, _)| *i != from);
+        rules.push((from, to));
+        self
+    }
+
+    pub fn remap(&self, level: &Level, target: &str) -> Level {
+        self.rules
+            .get(target)
+            .and_then(|rules| rules.iter().find(|(from, _)| from == level))
+            .map(|(_, to)| *to)
+            .unwrap_or(*level)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::remap::LevelRemap;
+    use tracing::Level;
+
+    #[test]
+    fn remaps_selected_targets() {
+        let remap = LevelRemap::new()
+            .with_rule("noisy", Level::WARN, Level::DEBUG)
+            .with_rule("auth::audit", Level::INFO, Level::WARN);
+
+        assert_eq!(remap.remap(&Level::WARN, "noisy::pool"), Level::DEBUG);
+        assert_eq!(remap.remap(&Level::ERROR, "noisy::pool"), Level::ERROR);
+        assert_eq!(remap.remap(&Level::INFO, "auth::audit"), Level::WARN);
+        assert_eq!(remap.remap(&Level::INFO, "auth"), Level::INFO);
+    }
+}
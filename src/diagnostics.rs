@@ -0,0 +1,122 @@
+use std::cell::Cell;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::sync::RwLock;
+
+type Handler = Box<dyn Fn(&Diagnostic<'_>) + Send + Sync>;
+
+static HANDLER: RwLock<Option<Handler>> = RwLock::new(None);
+
+thread_local! {
+    static REPORTING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Problems the crate runs into while logging, which can't be logged the usual way.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Diagnostic<'a> {
+    /// A pattern referenced a placeholder this crate doesn't know; it was left out.
+    UnknownPlaceholder { name: &'a str },
+    /// An appender failed to write, `target` is the event's target when known.
+    AppenderError {
+        target: Option<&'a str>,
+        error: &'a io::Error,
+    },
+    /// An appender failed to flush.
+    FlushError { error: &'a io::Error },
+    /// None of the appenders selected for an event wrote it.
+    EventDropped { target: &'a str },
+    /// An appender failed `failures` times in a row, its lines go to stderr for now.
+    AppenderFailover { failures: u32, error: &'a io::Error },
+    /// An appender that failed over to stderr is writing again.
+    AppenderRecovered,
+    /// A queueing appender dropped `dropped` lines since its last report because its queue
+    /// was full. Reported at most every few seconds, not per line.
+    QueueOverflow { dropped: u64 },
+    /// A background thread writing appender queues panicked, what it hadn't written yet
+    /// is lost.
+    WorkerPanicked { thread: &'a str, message: &'a str },
+    /// The default action of a signal couldn't be taken after shutting down on it.
+    SignalError { error: &'a io::Error },
+}
+
+impl Display for Diagnostic<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Diagnostic::UnknownPlaceholder { name } => {
+                write!(f, "unknown placeholder type `{}`", name)
+            }
+            Diagnostic::AppenderError {
+                target: Some(target),
+                error,
+            } => write!(
+                f,
+                "appender failed to write event from `{}`: {}",
+                target, error
+            ),
+            Diagnostic::AppenderError {
+                target: None,
+                error,
+            } => write!(f, "appender failed to write: {}", error),
+            Diagnostic::FlushError { error } => write!(f, "appender failed to flush: {}", error),
+            Diagnostic::EventDropped { target } => {
+                write!(f, "event from `{}` wasn't written by any appender", target)
+            }
+            Diagnostic::AppenderFailover { failures, error } => write!(
+                f,
+                "appender failed {} times in a row ({}), writing to stderr",
+                failures, error
+            ),
+            Diagnostic::AppenderRecovered => write!(f, "appender recovered, resuming"),
+            Diagnostic::QueueOverflow { dropped } => {
+                write!(f, "appender queue is full, dropped {} lines", dropped)
+            }
+            Diagnostic::WorkerPanicked { thread, message } => {
+                write!(f, "appender thread `{}` panicked: {}", thread, message)
+            }
+            Diagnostic::SignalError { error } => {
+                write!(
+                    f,
+                    "failed to take the default action of the signal: {}",
+                    error
+                )
+            }
+        }
+    }
+}
+
+/// Routes internal diagnostics to `handler` instead of stderr.
+///
+/// The handler may log through `tracing`: diagnostics raised while it runs are
+/// discarded instead of being reported again.
+pub fn set_handler<F: Fn(&Diagnostic<'_>) + Send + Sync + 'static>(handler: F) {
+    *HANDLER.write().unwrap_or_else(|e| e.into_inner()) = Some(Box::new(handler));
+}
+
+/// Restores the default handler, which prints diagnostics to stderr.
+pub fn reset_handler() {
+    *HANDLER.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Clears the reporting flag of the thread, also when the handler panics.
+struct Reporting<'a>(&'a Cell<bool>);
+
+impl Drop for Reporting<'_> {
+    fn drop(&mut self) {
+        self.0.set(false);
+    }
+}
+
+pub(crate) fn report(diagnostic: Diagnostic<'_>) {
+    let _ = REPORTING.try_with(|reporting| {
+        if reporting.replace(true) {
+            return;
+        }
+        let _reporting = Reporting(reporting);
+
+        match &*HANDLER.read().unwrap_or_else(|e| e.into_inner()) {
+            Some(handler) => handler(&diagnostic),
+            None => eprintln!("tracing-configurable: {}", diagnostic),
+        }
+    });
+}
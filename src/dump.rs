@@ -0,0 +1,105 @@
+use crate::appender::{OwnedRecord, Record};
+use crate::config::LayerConfig;
+use crate::diagnostics::{self, Diagnostic};
+use crate::renderer::RenderedLines;
+use crate::telemetry::Telemetry;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use tracing::Level;
+
+struct Entry {
+    record: OwnedRecord,
+    lines: RenderedLines,
+}
+
+/// Keeps the latest events the config filtered out, to be written once an error occurs.
+pub(crate) struct DumpBuffer {
+    level: Level,
+    capacity: usize,
+    entries: Mutex<VecDeque<Entry>>,
+}
+
+impl DumpBuffer {
+    pub(crate) fn new(level: Level, capacity: usize) -> Self {
+        Self {
+            level,
+            capacity: capacity.max(1),
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub(crate) fn captures(&self, level: &Level) -> bool {
+        *level <= self.level
+    }
+
+    /// Buffers `record` with the lines rendered for its appenders. Resolver values aren't
+    /// kept, running every resolver for events that are mostly never written costs too much.
+    pub(crate) fn push(&self, record: &Record<'_>, lines: RenderedLines) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+
+        entries.push_back(Entry {
+            record: OwnedRecord::new(record),
+            lines,
+        });
+    }
+
+    /// Writes the buffered events, oldest first, to the appenders the config returns for them.
+    ///
+    /// Lines are paired with appenders by position, so the config is expected to return the
+    /// same appenders for a level and target as when the event was buffered.
+    pub(crate) fn dump(&self, config: &dyn LayerConfig, telemetry: &Telemetry) {
+        let entries = std::mem::take(&mut *self.entries.lock().unwrap());
+
+        for entry in entries {
+            let record = entry.record.record();
+            let appenders = config.get_appenders(record.level(), record.target());
+
+            for (idx, appender) in appenders.iter().enumerate() {
+                let Some(line) = entry.lines.get(idx) else {
+                    continue;
+                };
+
+                if let Err(error) = appender.write_record(&record, line) {
+                    telemetry.record_appender_error();
+                    diagnostics::report(Diagnostic::AppenderError {
+                        target: Some(record.target()),
+                        error: &error,
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::{CaptureAppender, TestConfig};
+    use crate::ConfigurableLayer;
+    use tracing::{debug, error, info, Level};
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::registry;
+
+    #[test]
+    fn writes_filtered_events_before_error() {
+        let capture = CaptureAppender::new();
+        let layer =
+            ConfigurableLayer::new(TestConfig::new(capture.clone()).with_max_level(Level::INFO))
+                .with_dump_on_error(Level::DEBUG, 2);
+
+        tracing::subscriber::with_default(registry().with(layer), || {
+            debug!("step 1");
+            debug!("step 2");
+            info!("working");
+            debug!("step 3");
+            capture.assert_not_logged(Level::DEBUG, "tracing_configurable", "step");
+
+            error!("failed");
+        });
+
+        let messages: Vec<String> = capture.events().into_iter().map(|i| i.message).collect();
+        assert_eq!(messages, ["working", "step 2", "step 3", "failed"]);
+    }
+}
@@ -1,9 +1,19 @@
+use smallvec::SmallVec;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
+use std::ops::{Deref, DerefMut};
 use tracing::field::{debug, Field, Visit};
 
+/// Upper bound of idle visitors kept per thread.
+const MAX_POOLED: usize = 8;
+
+thread_local! {
+    static POOL: RefCell<Vec<FieldsVisitor>> = const { RefCell::new(Vec::new()) };
+}
+
+#[derive(Debug, Clone)]
 pub enum EventValue {
     F64(f64),
     I64(i64),
@@ -28,17 +38,55 @@ impl Display for EventValue {
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct FieldsVisitor {
     message: Option<String>,
-    values: HashMap<&'static str, Vec<EventValue>>,
+    // most fields are recorded once, keep that value inline
+    values: HashMap<&'static str, SmallVec<[EventValue; 1]>>,
 }
 
 impl FieldsVisitor {
+    /// Takes a visitor from the thread-local pool; it's cleared and returned to the pool on drop.
+    pub fn pooled() -> PooledFields {
+        let visitor = POOL
+            .try_with(|pool| pool.borrow_mut().pop())
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+
+        PooledFields(Some(visitor))
+    }
+
+    /// Fields holding only `message`, for records the crate writes itself.
+    pub(crate) fn with_message(message: String) -> Self {
+        Self {
+            message: Some(message),
+            ..Default::default()
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.message = None;
+        self.values.clear();
+    }
+
     pub fn message(&self) -> &str {
         self.message.as_deref().unwrap_or("")
     }
 
+    pub fn values(&self) -> impl Iterator<Item = (&'static str, &[EventValue])> {
+        self.values
+            .iter()
+            .map(|(key, values)| (*key, values.as_slice()))
+    }
+
+    /// Adds `value` for `name`, unless the event recorded `name` itself.
+    pub fn insert_if_absent(&mut self, name: &'static str, value: EventValue) {
+        self.values
+            .entry(name)
+            .or_insert_with(|| SmallVec::from_elem(value, 1));
+    }
+
     pub fn has_values(&self) -> bool {
         !self.values.is_empty()
     }
@@ -74,6 +122,37 @@ impl FieldsVisitor {
     }
 }
 
+pub struct PooledFields(Option<FieldsVisitor>);
+
+impl Deref for PooledFields {
+    type Target = FieldsVisitor;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledFields {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledFields {
+    fn drop(&mut self) {
+        if let Some(mut visitor) = self.0.take() {
+            visitor.clear();
+
+            let _ = POOL.try_with(|pool| {
+                let mut pool = pool.borrow_mut();
+                if pool.len() < MAX_POOLED {
+                    pool.push(visitor);
+                }
+            });
+        }
+    }
+}
+
 impl Visit for FieldsVisitor {
     fn record_f64(&mut self, field: &Field, value: f64) {
         if field.name() == "message" && self.message.is_none() {
@@ -167,3 +246,47 @@ impl Visit for FieldsVisitor {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::fields::{FieldsVisitor, MAX_POOLED, POOL};
+    use tracing::field::Visit;
+    use tracing::Level;
+
+    #[test]
+    fn reuses_cleared_visitors() {
+        let callsite = tracing::callsite! {
+            name: "test",
+            kind: tracing::metadata::Kind::EVENT,
+            target: "my_app",
+            level: Level::INFO,
+            fields: message, user
+        };
+        let metadata = tracing::callsite::Callsite::metadata(callsite);
+        let message = metadata.fields().field("message").unwrap();
+        let user = metadata.fields().field("user").unwrap();
+
+        let capacity = {
+            let mut fields = FieldsVisitor::pooled();
+            fields.record_str(&message, "hello");
+            fields.record_str(&user, "alice");
+            assert!(!fields.values["user"].spilled());
+
+            fields.record_u64(&user, 7);
+            assert!(fields.values["user"].spilled());
+            fields.values.capacity()
+        };
+
+        let fields = FieldsVisitor::pooled();
+        assert_eq!(fields.message(), "");
+        assert!(!fields.has_values());
+        assert_eq!(fields.values.capacity(), capacity);
+        drop(fields);
+
+        let many = (0..MAX_POOLED + 2)
+            .map(|_| FieldsVisitor::pooled())
+            .collect::<Vec<_>>();
+        drop(many);
+        assert_eq!(POOL.with(|i| i.borrow().len()), MAX_POOLED);
+    }
+}
@@ -0,0 +1,225 @@
+use crate::appender::Record;
+use crate::fields::{EventValue, FieldsVisitor};
+use chrono::{DateTime, Local, SecondsFormat};
+use std::fmt::Write;
+use tracing::Level;
+
+const INDENT: &str = "  ";
+const RESET: &str = "\x1b[0m";
+const KEY: &str = "\x1b[36m";
+const STRING: &str = "\x1b[32m";
+const SCALAR: &str = "\x1b[33m";
+
+/// Layout of JSON output. Compact single-line objects are the default; the pretty form,
+/// optionally colored, is meant for reading logs in a local console.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct JsonFormat {
+    pretty: bool,
+    colored: bool,
+}
+
+impl JsonFormat {
+    /// One object per line, for log shippers and other machines.
+    pub fn compact() -> Self {
+        Self::default()
+    }
+
+    /// Objects indented over several lines.
+    pub fn pretty() -> Self {
+        Self {
+            pretty: true,
+            colored: false,
+        }
+    }
+
+    /// Colors keys and values with ANSI escapes, the level by its severity.
+    pub fn colored(mut self, colored: bool) -> Self {
+        self.colored = colored;
+        self
+    }
+}
+
+/// Writes `record` as a JSON object: timestamp, level, target, message and the event fields.
+///
+/// `now` is only used for records that don't carry the time of their event.
+pub(crate) fn write_record(
+    out: &mut String,
+    record: &Record<'_>,
+    now: &DateTime<Local>,
+    format: JsonFormat,
+) {
+    write_object(
+        out,
+        format,
+        &record.time().unwrap_or(*now),
+        Some((record.level(), record.target())),
+        record.fields().message(),
+        record.fields(),
+        &record.resolved_fields(),
+    );
+}
+
+/// Writes a line that came without an event as a JSON object holding just its timestamp
+/// and message.
+pub(crate) fn write_message(
+    out: &mut String,
+    message: &str,
+    now: &DateTime<Local>,
+    format: JsonFormat,
+) {
+    write_object(
+        out,
+        format,
+        now,
+        None,
+        message,
+        &FieldsVisitor::default(),
+        &[],
+    );
+}
+
+fn write_object(
+    out: &mut String,
+    format: JsonFormat,
+    now: &DateTime<Local>,
+    event: Option<(&Level, &str)>,
+    message: &str,
+    fields: &FieldsVisitor,
+    resolved: &[(&'static str, EventValue)],
+) {
+    out.push('{');
+    write_key(out, format, 1, true, "timestamp");
+    let timestamp = now.to_rfc3339_opts(SecondsFormat::Micros, true);
+    colored(out, format, STRING, |out| write_str(out, &timestamp));
+
+    if let Some((level, target)) = event {
+        write_key(out, format, 1, false, "level");
+        colored(out, format, level_color(level), |out| {
+            write_str(out, level.as_str())
+        });
+        write_key(out, format, 1, false, "target");
+        colored(out, format, STRING, |out| write_str(out, target));
+    }
+
+    write_key(out, format, 1, false, "message");
+    colored(out, format, STRING, |out| write_str(out, message));
+
+    if fields.has_values() || !resolved.is_empty() {
+        write_key(out, format, 1, false, "fields");
+        out.push('{');
+
+        let resolved = resolved
+            .iter()
+            .map(|(key, value)| (*key, std::slice::from_ref(value)));
+        for (idx, (key, values)) in fields.values().chain(resolved).enumerate() {
+            write_key(out, format, 2, idx == 0, key);
+            match values {
+                [value] => write_value(out, format, value),
+                values => {
+                    out.push('[');
+                    for (idx, value) in values.iter().enumerate() {
+                        if idx > 0 {
+                            out.push_str(if format.pretty { ", " } else { "," });
+                        }
+                        write_value(out, format, value);
+                    }
+                    out.push(']');
+                }
+            }
+        }
+
+        new_line(out, format, 1);
+        out.push('}');
+    }
+
+    new_line(out, format, 0);
+    out.push('}');
+}
+
+/// Writes the separator and indentation ahead of an object entry, then its key.
+fn write_key(out: &mut String, format: JsonFormat, depth: usize, first: bool, key: &str) {
+    if !first {
+        out.push(',');
+    }
+    new_line(out, format, depth);
+
+    colored(out, format, KEY, |out| write_str(out, key));
+    out.push(':');
+    if format.pretty {
+        out.push(' ');
+    }
+}
+
+fn new_line(out: &mut String, format: JsonFormat, depth: usize) {
+    if format.pretty {
+        out.push('\n');
+        for _ in 0..depth {
+            out.push_str(INDENT);
+        }
+    }
+}
+
+fn colored<F: FnOnce(&mut String)>(out: &mut String, format: JsonFormat, color: &str, write: F) {
+    if format.colored {
+        out.push_str(color);
+        write(out);
+        out.push_str(RESET);
+    } else {
+        write(out);
+    }
+}
+
+fn level_color(level: &Level) -> &'static str {
+    match *level {
+        Level::ERROR => "\x1b[31m",
+        Level::WARN => "\x1b[33m",
+        Level::INFO => "\x1b[32m",
+        Level::DEBUG => "\x1b[34m",
+        Level::TRACE => "\x1b[35m",
+    }
+}
+
+fn write_value(out: &mut String, format: JsonFormat, value: &EventValue) {
+    let color = match value {
+        EventValue::String(_) => STRING,
+        EventValue::F64(v) if !v.is_finite() => STRING,
+        _ => SCALAR,
+    };
+
+    colored(out, format, color, |out| {
+        let _ = match value {
+            EventValue::F64(v) if v.is_finite() => write!(out, "{}", v),
+            EventValue::F64(v) => {
+                write_str(out, &v.to_string());
+                Ok(())
+            }
+            EventValue::I64(v) => write!(out, "{}", v),
+            EventValue::U64(v) => write!(out, "{}", v),
+            EventValue::I128(v) => write!(out, "{}", v),
+            EventValue::U128(v) => write!(out, "{}", v),
+            EventValue::Bool(v) => write!(out, "{}", v),
+            EventValue::String(v) => {
+                write_str(out, v);
+                Ok(())
+            }
+        };
+    });
+}
+
+fn write_str(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
@@ -1,22 +1,181 @@
 #![allow(dead_code)]
 
-use crate::config::LayerConfig;
-use crate::fields::FieldsVisitor;
-use crate::renderer::EventRenderer;
+use crate::appender::Appender;
+use crate::boost::{BoostHandle, Boosts};
+use crate::clock::Clock;
+use crate::config::{LayerConfig, SpanScope};
+use crate::diagnostics::Diagnostic;
+use crate::dump::DumpBuffer;
+use crate::fields::{EventValue, FieldsVisitor};
+use crate::renderer::{EventContext, RenderedLines};
+use crate::resolver::Resolvers;
+use crate::shutdown::{ShutdownHandle, ShutdownState};
+use crate::telemetry::Telemetry;
+use std::io;
+use std::sync::Arc;
 use tracing::span::{Attributes, Id};
-use tracing::{Event, Subscriber};
+use tracing::{Event, Level, Metadata, Subscriber};
 use tracing_subscriber::layer::Context;
 use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::Layer;
 
 pub mod appender;
+mod backtrace;
+pub mod boost;
+pub mod buffer;
+pub mod clock;
 pub mod config;
+pub mod diagnostics;
+mod dump;
 pub mod fields;
+mod json;
+pub mod mdc;
+#[cfg(feature = "opentelemetry")]
+mod otel;
+pub mod panic;
 pub mod pattern;
 pub mod renderer;
+pub mod resolver;
+pub mod shutdown;
+pub mod telemetry;
+pub mod testing;
 
-struct ConfigurableLayer {
-    config: Box<dyn LayerConfig>,
+pub struct ConfigurableLayer {
+    config: Arc<dyn LayerConfig>,
+    telemetry: Arc<Telemetry>,
+    clock: Arc<dyn Clock>,
+    shutdown: Arc<ShutdownState>,
+    boosts: Arc<Boosts>,
+    dump: Option<DumpBuffer>,
+    static_fields: Vec<(&'static str, EventValue)>,
+    resolvers: Resolvers,
+}
+
+impl ConfigurableLayer {
+    pub fn new<C: LayerConfig + 'static>(config: C) -> Self {
+        Self {
+            config: Arc::new(config),
+            telemetry: Default::default(),
+            clock: clock::system(),
+            shutdown: Default::default(),
+            boosts: Arc::new(Boosts::new(clock::system())),
+            dump: None,
+            static_fields: Vec::new(),
+            resolvers: Resolvers::new(),
+        }
+    }
+
+    /// Replaces the system clock, see [`clock::system`]. Appenders that need the time on
+    /// their own, e.g. to name rolled files, take the same clock through their options.
+    ///
+    /// Boosts expire by this clock too; handles taken before the clock is replaced don't
+    /// affect the layer anymore.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self.boosts = Arc::new(Boosts::new(self.clock.clone()));
+        self
+    }
+
+    /// Keeps up to `capacity` of the latest events the config filters out, as long as they're
+    /// at `level` or more severe, and writes them ahead of the next ERROR event.
+    pub fn with_dump_on_error(mut self, level: Level, capacity: usize) -> Self {
+        self.dump = Some(DumpBuffer::new(level, capacity));
+        self
+    }
+
+    /// Adds `name` to the fields of every event, e.g. the service name or version.
+    ///
+    /// Fields recorded by the event itself take precedence.
+    pub fn with_static_field<V: ToString>(mut self, name: &'static str, value: V) -> Self {
+        self.static_fields
+            .push((name, EventValue::String(value.to_string())));
+        self
+    }
+
+    /// Adds `name` with the value of the environment variable `var`, if it's set,
+    /// e.g. the pod name in Kubernetes.
+    pub fn with_env_field(self, name: &'static str, var: &str) -> Self {
+        match std::env::var(var) {
+            Ok(value) => self.with_static_field(name, value),
+            Err(_) => self,
+        }
+    }
+
+    /// Registers a value computed per event, available to patterns as `$field(name = ...)`
+    /// and to renderers through [`EventContext::field`].
+    ///
+    /// The resolver only runs for events whose rendering asks for it.
+    pub fn with_resolver<F>(mut self, name: &'static str, resolver: F) -> Self
+    where
+        F: Fn(&'static Metadata<'static>) -> Option<EventValue> + Send + Sync + 'static,
+    {
+        self.resolvers.register(name, resolver);
+        self
+    }
+
+    fn event_context<'a, S>(
+        &'a self,
+        event: &'a Event<'a>,
+        ctx: &'a Context<'a, S>,
+        level: Level,
+    ) -> EventContext<'a, S>
+    where
+        S: Subscriber + for<'l> LookupSpan<'l>,
+    {
+        EventContext::new(event, ctx, &*self.clock)
+            .with_level(level)
+            .with_static_fields(&self.static_fields)
+            .with_resolvers(&self.resolvers)
+    }
+
+    /// Appenders the config routes the event to, looking at its spans if the config asks for it.
+    fn get_appenders<S>(
+        &self,
+        event: &Event<'_>,
+        ctx: &Context<'_, S>,
+        level: &Level,
+    ) -> Vec<Box<dyn Appender>>
+    where
+        S: Subscriber + for<'l> LookupSpan<'l>,
+    {
+        let target = event.metadata().target();
+        if !self.config.routes_by_span() {
+            return self.config.get_appenders(level, target);
+        }
+
+        let spans = ctx
+            .event_scope(event)
+            .map(|i| i.collect::<Vec<_>>())
+            .unwrap_or_default();
+        let extensions = spans.iter().map(|i| i.extensions()).collect::<Vec<_>>();
+        let scope = SpanScope::new(
+            spans
+                .iter()
+                .zip(&extensions)
+                .map(|(span, extensions)| (span.name(), extensions.get::<FieldsVisitor>()))
+                .collect(),
+        );
+
+        self.config.get_span_appenders(level, target, &scope)
+    }
+
+    /// Whether `config` or an active boost lets the event through.
+    fn enabled(&self, level: &Level, target: &str) -> bool {
+        self.config.enabled(level, target) || self.boosts.enabled(level, target)
+    }
+
+    /// Shared handle to the layer's logging counters, usable after the layer is installed.
+    pub fn telemetry(&self) -> Arc<Telemetry> {
+        self.telemetry.clone()
+    }
+
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle::new(self.config.clone(), self.shutdown.clone())
+    }
+
+    pub fn boost_handle(&self) -> BoostHandle {
+        BoostHandle::new(self.boosts.clone())
+    }
 }
 
 impl<S> Layer<S> for ConfigurableLayer
@@ -34,27 +193,93 @@ where
     }
 
     fn event_enabled(&self, event: &Event<'_>, _: Context<'_, S>) -> bool {
-        self.config
-            .enabled(event.metadata().level(), event.metadata().target())
+        let target = event.metadata().target();
+        let level = &self.config.remap_level(event.metadata().level(), target);
+
+        !self.shutdown.is_stopped()
+            && (self.enabled(level, target)
+                || self.dump.as_ref().is_some_and(|i| i.captures(level)))
     }
 
     fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
-        let appenders = self
-            .config
-            .get_appenders(event.metadata().level(), event.metadata().target());
-        for appender in appenders {
-            let pattern = appender.pattern();
-            if let Some(v) = pattern.render(event, &ctx) {
-                appender.write(&v)
+        let Some(_in_flight) = self.shutdown.enter() else {
+            return;
+        };
+
+        let target = event.metadata().target();
+        let level = &self.config.remap_level(event.metadata().level(), target);
+
+        if let Some(dump) = &self.dump {
+            if !self.enabled(level, target) {
+                let appenders = self.config.get_appenders(level, target);
+                if !appenders.is_empty() {
+                    let event = self.event_context(event, &ctx, *level);
+                    let lines = RenderedLines::render(&appenders, &event);
+                    dump.push(&event.record(), lines);
+                }
+
+                return;
+            }
+
+            if *level == Level::ERROR {
+                dump.dump(&*self.config, &self.telemetry);
             }
         }
+
+        self.telemetry.record_event(level, target);
+
+        let mut written = false;
+        let mut overflowed = false;
+        let appenders = self.get_appenders(event, &ctx, level);
+        if !appenders.is_empty() {
+            let event = self.event_context(event, &ctx, *level);
+            let record = event.record();
+
+            let lines = RenderedLines::render(&appenders, &event);
+            for (idx, appender) in appenders.iter().enumerate() {
+                if let Some(v) = lines.get(idx) {
+                    match appender.write_record(&record, v) {
+                        Ok(()) => written = true,
+                        // the queue reports its drops in summaries, not per event
+                        Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+                            self.telemetry.record_overflow();
+                            overflowed = true;
+                        }
+                        Err(error) => {
+                            self.telemetry.record_appender_error();
+                            diagnostics::report(Diagnostic::AppenderError {
+                                target: Some(target),
+                                error: &error,
+                            });
+                        }
+                    }
+                }
+            }
+
+            // the process may be about to abort, don't leave the panic in a queue
+            if panic::is_logging() {
+                for appender in &appenders {
+                    if let Err(error) = appender.flush() {
+                        diagnostics::report(Diagnostic::FlushError { error: &error });
+                    }
+                }
+            }
+
+            if !written && !overflowed {
+                diagnostics::report(Diagnostic::EventDropped { target });
+            }
+        }
+
+        if !written {
+            self.telemetry.record_dropped();
+        }
     }
 
-    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
-        ctx.span(id)
-            .expect("span not found")
-            .extensions_mut()
-            .remove::<FieldsVisitor>();
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        // spans can be entered again after exiting, so the fields stay until the span closes
+        if let Some(span) = ctx.span(&id) {
+            span.extensions_mut().remove::<FieldsVisitor>();
+        }
     }
 }
 
@@ -80,9 +305,8 @@ mod test {
                 &self.pattern
             }
 
-            fn write(&self, value: &str) {
-                let _ = writeln!(stdout().lock(), "{}", value);
-                // let _ = stdout().lock().write(value.as_bytes());
+            fn write(&self, value: &str) -> std::io::Result<()> {
+                writeln!(stdout().lock(), "{}", value)
             }
         }
 
@@ -104,9 +328,7 @@ mod test {
         }
 
         registry()
-            .with(ConfigurableLayer {
-                config: Box::new(TestConfig {}),
-            })
+            .with(ConfigurableLayer::new(TestConfig {}))
             .init();
 
         let test = trace_span!("test", arg = 1, arg = "test").entered();
@@ -0,0 +1,267 @@
+use pin_project_lite::pin_project;
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+thread_local! {
+    static MDC: RefCell<Vec<(String, String)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Sets `key` in the current thread's diagnostic context, rendered by `$mdc(name = ...)`,
+/// until the returned guard is dropped, which restores the previous value (if any).
+#[must_use = "the value is removed when the guard is dropped"]
+pub fn insert<K: Into<String>, V: Into<String>>(key: K, value: V) -> MdcGuard {
+    let key = key.into();
+    let value = value.into();
+
+    let previous = MDC.with(|mdc| {
+        let mut mdc = mdc.borrow_mut();
+        match mdc.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => Some(std::mem::replace(v, value)),
+            None => {
+                mdc.push((key.clone(), value));
+                None
+            }
+        }
+    });
+
+    MdcGuard { key, previous }
+}
+
+pub fn get(key: &str) -> Option<String> {
+    MDC.with(|mdc| {
+        mdc.borrow()
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.clone())
+    })
+}
+
+pub fn remove(key: &str) -> Option<String> {
+    MDC.with(|mdc| {
+        let mut mdc = mdc.borrow_mut();
+        let idx = mdc.iter().position(|(k, _)| k == key)?;
+        Some(mdc.remove(idx).1)
+    })
+}
+
+pub fn clear() {
+    MDC.with(|mdc| mdc.borrow_mut().clear())
+}
+
+pub fn is_empty() -> bool {
+    MDC.with(|mdc| mdc.borrow().is_empty())
+}
+
+/// Renders every entry as `key=`value`` pairs, in insertion order.
+pub fn format_values() -> String {
+    MDC.with(|mdc| {
+        mdc.borrow()
+            .iter()
+            .map(|(k, v)| format!("{}=`{}`", k, v))
+            .collect::<Vec<_>>()
+            .join(",")
+    })
+}
+
+/// Copy of the current thread's context, in insertion order.
+pub fn snapshot() -> Vec<(String, String)> {
+    MDC.with(|mdc| mdc.borrow().clone())
+}
+
+fn swap(context: &mut Vec<(String, String)>) {
+    let _ = MDC.try_with(|mdc| std::mem::swap(&mut *mdc.borrow_mut(), context));
+}
+
+/// Context of an [`MdcFuture`], holding the thread's context while the task's is swapped in.
+struct TaskContext {
+    values: Vec<(String, String)>,
+    entered: bool,
+}
+
+impl TaskContext {
+    fn enter(&mut self) {
+        if !self.entered {
+            swap(&mut self.values);
+            self.entered = true;
+        }
+    }
+
+    fn leave(&mut self) {
+        if self.entered {
+            swap(&mut self.values);
+            self.entered = false;
+        }
+    }
+}
+
+impl Drop for TaskContext {
+    fn drop(&mut self) {
+        self.leave();
+    }
+}
+
+pub trait WithMdc: Future + Sized {
+    /// Carries the current thread's context along with the future, so it's still set
+    /// after `.await` points and when the future is spawned onto another thread.
+    fn with_mdc(self) -> MdcFuture<Self> {
+        MdcFuture::new(self, snapshot())
+    }
+}
+
+impl<F: Future> WithMdc for F {}
+
+pin_project! {
+    /// Future with its own (task-local) diagnostic context.
+    ///
+    /// The context is swapped in for the duration of every poll, and while the inner
+    /// future is dropped; changes made by the future are kept for its next poll and don't
+    /// leak into the polling thread.
+    pub struct MdcFuture<F> {
+        #[pin]
+        inner: F,
+        // declared after `inner`, so it's dropped (and the thread's context restored) last
+        context: TaskContext,
+    }
+
+    impl<F> PinnedDrop for MdcFuture<F> {
+        fn drop(this: Pin<&mut Self>) {
+            // guards held by the inner future are dropped with it and restore their
+            // previous values in the task's context
+            this.project().context.enter();
+        }
+    }
+}
+
+impl<F> MdcFuture<F> {
+    pub fn new(inner: F, context: Vec<(String, String)>) -> Self {
+        Self {
+            inner,
+            context: TaskContext {
+                values: context,
+                entered: false,
+            },
+        }
+    }
+}
+
+impl<F: Future> Future for MdcFuture<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        struct Restore<'a>(&'a mut TaskContext);
+
+        impl Drop for Restore<'_> {
+            fn drop(&mut self) {
+                self.0.leave();
+            }
+        }
+
+        let this = self.project();
+        this.context.enter();
+        let _restore = Restore(this.context);
+
+        this.inner.poll(cx)
+    }
+}
+
+pub struct MdcGuard {
+    key: String,
+    previous: Option<String>,
+}
+
+impl Drop for MdcGuard {
+    fn drop(&mut self) {
+        let key = std::mem::take(&mut self.key);
+        let previous = self.previous.take();
+
+        let _ = MDC.try_with(|mdc| {
+            let mut mdc = mdc.borrow_mut();
+            let idx = mdc.iter().position(|(k, _)| *k == key);
+
+            match (idx, previous) {
+                (Some(idx), Some(previous)) => mdc[idx].1 = previous,
+                (Some(idx), None) => {
+                    mdc.remove(idx);
+                }
+                (None, Some(previous)) => mdc.push((key, previous)),
+                (None, None) => {}
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mdc;
+
+    #[test]
+    fn guard_restores_previous_value() {
+        let _outer = mdc::insert("request_id", "1");
+        {
+            let _inner = mdc::insert("request_id", "2");
+            let _user = mdc::insert("user", "alice");
+            assert_eq!(mdc::format_values(), "request_id=`2`,user=`alice`");
+        }
+
+        assert_eq!(mdc::get("request_id").as_deref(), Some("1"));
+        assert_eq!(mdc::get("user"), None);
+    }
+
+    #[test]
+    fn future_keeps_context_across_polls() {
+        use crate::mdc::WithMdc;
+        use std::future::Future;
+        use std::task::{Context, Poll, Waker};
+
+        // the guard lives across the yield point, like a guard held over an `.await`
+        let mut guard = None;
+        let fut = std::future::poll_fn(move |_| {
+            if guard.is_none() {
+                guard = Some(mdc::insert("step", "1"));
+                return Poll::Pending;
+            }
+
+            Poll::Ready((mdc::get("task"), mdc::get("step")))
+        });
+
+        let fut = {
+            let _task = mdc::insert("task", "a");
+            fut.with_mdc()
+        };
+
+        let mut fut = std::pin::pin!(fut);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        assert!(fut.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(mdc::get("step"), None);
+
+        let ready = fut.as_mut().poll(&mut cx);
+        let expected = (Some("a".to_string()), Some("1".to_string()));
+        assert_eq!(ready, Poll::Ready(expected));
+        assert_eq!(mdc::get("task"), None);
+    }
+
+    #[test]
+    fn future_drops_guards_in_its_context() {
+        use crate::mdc::WithMdc;
+        use std::future::Future;
+        use std::task::{Context, Poll, Waker};
+
+        let mut guards = Vec::new();
+        let fut = std::future::poll_fn(move |_| {
+            guards.push(mdc::insert("step", "1"));
+            Poll::<()>::Pending
+        })
+        .with_mdc();
+
+        let mut fut = Box::pin(fut);
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(fut.as_mut().poll(&mut cx).is_pending());
+
+        let _step = mdc::insert("step", "thread");
+        drop(fut);
+        assert_eq!(mdc::get("step").as_deref(), Some("thread"));
+    }
+}
@@ -0,0 +1,154 @@
+use opentelemetry::trace::{SamplingDecision, SpanId, TraceContextExt, TraceFlags, TraceId};
+use tracing_opentelemetry::OtelData;
+use tracing_subscriber::registry::{LookupSpan, SpanRef};
+
+pub fn trace_id<S>(span: &SpanRef<'_, S>) -> Option<TraceId>
+where
+    S: for<'l> LookupSpan<'l>,
+{
+    let extensions = span.extensions();
+    let otel = extensions.get::<OtelData>()?;
+
+    // children inherit the trace id of their parent context, which may have been set after
+    // the span was created, e.g. from an incoming `traceparent`; root spans carry their own
+    let parent = otel.parent_cx.span();
+    let trace_id = if otel.parent_cx.has_active_span() {
+        parent.span_context().trace_id()
+    } else {
+        otel.builder.trace_id.unwrap_or(TraceId::INVALID)
+    };
+
+    if trace_id == TraceId::INVALID {
+        None
+    } else {
+        Some(trace_id)
+    }
+}
+
+pub fn span_id<S>(span: &SpanRef<'_, S>) -> Option<SpanId>
+where
+    S: for<'l> LookupSpan<'l>,
+{
+    let extensions = span.extensions();
+    let otel = extensions.get::<OtelData>()?;
+
+    otel.builder.span_id.filter(|i| *i != SpanId::INVALID)
+}
+
+/// Builds a W3C `traceparent` value (`00-<trace-id>-<parent-id>-<flags>`) for the span.
+pub fn traceparent<S>(span: &SpanRef<'_, S>) -> Option<String>
+where
+    S: for<'l> LookupSpan<'l>,
+{
+    let trace_id = trace_id(span)?;
+    let span_id = span_id(span)?;
+
+    let extensions = span.extensions();
+    let otel = extensions.get::<OtelData>()?;
+
+    // spans that haven't been sampled yet get the decision of the SDK's default sampler:
+    // their parent's, or sampled for root spans
+    let flags = match &otel.builder.sampling_result {
+        Some(result) if result.decision == SamplingDecision::RecordAndSample => TraceFlags::SAMPLED,
+        Some(_) => TraceFlags::default(),
+        None if otel.parent_cx.has_active_span() => {
+            otel.parent_cx.span().span_context().trace_flags()
+        }
+        None => TraceFlags::SAMPLED,
+    };
+
+    Some(format!("00-{}-{}-{:02x}", trace_id, span_id, flags))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::pattern::{Pattern, PatternItem, Placeholder, PlaceholderType};
+    use crate::testing::{CaptureAppender, TestConfig};
+    use crate::ConfigurableLayer;
+    use opentelemetry::trace::noop::NoopSpan;
+    use opentelemetry::trace::{
+        SpanBuilder, SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState, Tracer,
+    };
+    use opentelemetry::Context;
+    use std::collections::HashMap;
+    use tracing::info;
+    use tracing_opentelemetry::{OpenTelemetrySpanExt, OtelData, PreSampledTracer};
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::registry;
+
+    const TRACE_ID: u128 = 0x4bf92f3577b34da6a3ce929d0e0e4736;
+    const SPAN_ID: u64 = 0x00f067aa0ba902b7;
+
+    /// Hands out fixed ids and exports nothing.
+    struct FixedIdTracer;
+
+    impl Tracer for FixedIdTracer {
+        type Span = NoopSpan;
+
+        fn build_with_context(&self, _: SpanBuilder, _: &Context) -> Self::Span {
+            NoopSpan::DEFAULT
+        }
+    }
+
+    impl PreSampledTracer for FixedIdTracer {
+        fn sampled_context(&self, data: &mut OtelData) -> Context {
+            data.parent_cx.clone()
+        }
+
+        fn new_trace_id(&self) -> TraceId {
+            TraceId::from(TRACE_ID)
+        }
+
+        fn new_span_id(&self) -> SpanId {
+            SpanId::from(SPAN_ID)
+        }
+    }
+
+    #[test]
+    fn renders_traceparent_of_current_span() {
+        let capture = CaptureAppender::with_pattern(Pattern::new(vec![PatternItem::Placeholder(
+            Placeholder::new(PlaceholderType::TraceParent, HashMap::new(), vec![]),
+        )]));
+        let subscriber = registry()
+            .with(tracing_opentelemetry::layer().with_tracer(FixedIdTracer))
+            .with(ConfigurableLayer::new(TestConfig::new(capture.clone())));
+
+        let remote_trace = TraceId::from(0x0af7651916cd43dd8448eb211c80319c);
+        tracing::subscriber::with_default(subscriber, || {
+            info!("outside");
+
+            tracing::info_span!("root").in_scope(|| info!("root"));
+
+            for flags in [TraceFlags::SAMPLED, TraceFlags::default()] {
+                let child = tracing::info_span!("child");
+                child.set_parent(Context::new().with_remote_span_context(SpanContext::new(
+                    remote_trace,
+                    SpanId::from(0xb7ad6b7169203331),
+                    flags,
+                    true,
+                    TraceState::default(),
+                )));
+                child.in_scope(|| info!("child"));
+            }
+        });
+
+        let rendered = capture
+            .events()
+            .into_iter()
+            .map(|i| i.rendered)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rendered,
+            [
+                String::new(),
+                format!(
+                    "00-{}-{}-01",
+                    TraceId::from(TRACE_ID),
+                    SpanId::from(SPAN_ID)
+                ),
+                format!("00-{}-{}-01", remote_trace, SpanId::from(SPAN_ID)),
+                format!("00-{}-{}-00", remote_trace, SpanId::from(SPAN_ID)),
+            ]
+        );
+    }
+}
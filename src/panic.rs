@@ -0,0 +1,130 @@
+use std::backtrace::Backtrace;
+use std::cell::Cell;
+use std::panic::PanicHookInfo;
+
+/// Target of the events emitted by the panic hook, e.g. to route them. Appenders receiving
+/// the hook's events are flushed right away, before the panic unwinds or aborts the process;
+/// other events with this target are written as usual.
+pub const TARGET: &str = "panic";
+
+thread_local! {
+    // set while the hook logs on this thread
+    static LOGGING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Whether the current event comes from the panic hook.
+pub(crate) fn is_logging() -> bool {
+    LOGGING.try_with(Cell::get).unwrap_or(false)
+}
+
+/// Installs a panic hook that logs panics as ERROR events (message, location and
+/// backtrace) through the installed subscriber.
+///
+/// The hook installed before, e.g. the default one printing to stderr or one from a crash
+/// reporter, still runs after the event is logged.
+pub fn install_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let message = payload(info);
+        let location = info
+            .location()
+            .map(|i| format!("{}:{}:{}", i.file(), i.line(), i.column()))
+            .unwrap_or_default();
+        let thread = std::thread::current();
+        let backtrace = Backtrace::force_capture();
+
+        // a panic while logging aborts the process, so the flag can't be left set
+        let _ = LOGGING.try_with(|i| i.set(true));
+        tracing::error!(
+            target: TARGET,
+            thread = thread.name().unwrap_or("<unnamed>"),
+            location,
+            backtrace = %backtrace,
+            "panicked: {}",
+            message
+        );
+        let _ = LOGGING.try_with(|i| i.set(false));
+
+        previous(info);
+    }));
+}
+
+fn payload<'a>(info: &'a PanicHookInfo<'_>) -> &'a str {
+    if let Some(v) = info.payload().downcast_ref::<&str>() {
+        v
+    } else if let Some(v) = info.payload().downcast_ref::<String>() {
+        v
+    } else {
+        "Box<dyn Any>"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::appender::{Appender, Record};
+    use crate::panic::{install_hook, TARGET};
+    use crate::pattern::Pattern;
+    use crate::testing::{CaptureAppender, TestConfig};
+    use crate::ConfigurableLayer;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tracing::{error, Level};
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::registry;
+
+    struct CountFlushes {
+        inner: CaptureAppender,
+        flushes: Arc<AtomicUsize>,
+    }
+
+    impl Appender for CountFlushes {
+        fn pattern(&self) -> &Pattern {
+            self.inner.pattern()
+        }
+
+        fn write(&self, value: &str) -> std::io::Result<()> {
+            self.inner.write(value)
+        }
+
+        fn write_record(&self, record: &Record<'_>, value: &str) -> std::io::Result<()> {
+            self.inner.write_record(record, value)
+        }
+
+        fn flush(&self) -> std::io::Result<()> {
+            self.flushes.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn logs_panics_and_runs_previous_hook() {
+        let previous = Arc::new(AtomicBool::new(false));
+        std::panic::set_hook({
+            let previous = previous.clone();
+            Box::new(move |_| previous.store(true, Ordering::SeqCst))
+        });
+        install_hook();
+
+        let capture = CaptureAppender::new();
+        let flushes = Arc::new(AtomicUsize::new(0));
+        let appender = CountFlushes {
+            inner: capture.clone(),
+            flushes: flushes.clone(),
+        };
+        let layer = ConfigurableLayer::new(TestConfig::new(appender));
+
+        tracing::subscriber::with_default(registry().with(layer), || {
+            error!(target: TARGET, "not a panic");
+            assert_eq!(flushes.load(Ordering::SeqCst), 0);
+
+            let result = std::panic::catch_unwind(|| panic!("boom"));
+            assert!(result.is_err());
+        });
+        drop(std::panic::take_hook());
+
+        capture.assert_logged(Level::ERROR, TARGET, "panicked: boom");
+        assert_eq!(capture.events().len(), 2);
+        assert_eq!(flushes.load(Ordering::SeqCst), 1);
+        assert!(previous.load(Ordering::SeqCst));
+    }
+}
@@ -1,25 +1,45 @@
+use crate::clock::Clock;
 use crate::fields::FieldsVisitor;
-use crate::renderer::EventRenderer;
-use chrono::Local;
-use once_cell::sync::Lazy;
+use crate::renderer::{EventContext, EventRenderer};
+use crate::{buffer, mdc};
+use chrono::{DateTime, Local};
 use std::borrow::Cow;
-use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt::Write;
-use tracing::{Event, Subscriber};
-use tracing_subscriber::layer::Context;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tracing::{Level, Subscriber};
 use tracing_subscriber::registry::LookupSpan;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
+#[cfg(feature = "parse")]
+use crate::diagnostics::{self, Diagnostic};
 #[cfg(feature = "parse")]
 use argable_parser::item::{Arg, Item, Value};
 
+pub mod log4j;
+
+#[derive(Debug)]
 pub struct Pattern {
     items: Vec<PatternItem>,
+    escape_control: bool,
 }
 
 impl Pattern {
     pub fn new(items: Vec<PatternItem>) -> Self {
-        Self { items }
+        Self {
+            items,
+            escape_control: false,
+        }
+    }
+
+    /// Escapes control characters (including newlines and ANSI escapes) found in messages,
+    /// fields, span arguments and MDC values, e.g. `\x1b` is written as `\u{1b}`.
+    ///
+    /// Guards against log injection and terminal escape attacks through user-supplied strings.
+    pub fn with_escaped_control(mut self, escape: bool) -> Self {
+        self.escape_control = escape;
+        self
     }
 
     #[cfg(feature = "parse")]
@@ -32,7 +52,7 @@ impl Pattern {
                 Item::Text(v) => Some(PatternItem::Text(v)),
                 Item::Placeholder(v) => {
                     let ty = PlaceholderType::from_str(v.name).or_else(|| {
-                        eprintln!("unknown placeholder type");
+                        diagnostics::report(Diagnostic::UnknownPlaceholder { name: v.name });
                         None
                     })?;
 
@@ -52,11 +72,9 @@ impl Pattern {
                         }
                     }
 
-                    Some(PatternItem::Placeholder(Placeholder {
-                        ty,
-                        properties,
-                        flags,
-                    }))
+                    Some(PatternItem::Placeholder(Placeholder::new(
+                        ty, properties, flags,
+                    )))
                 }
             })
             .collect();
@@ -64,6 +82,100 @@ impl Pattern {
         Ok(Self::new(items))
     }
 
+    /// Parses a log4j/logback layout string such as `%d %-5p [%t] %c - %m%n`,
+    /// see [`log4j`] for the supported conversions.
+    pub fn try_parse_log4j<S: AsRef<str>>(str: S) -> Result<Self, anyhow::Error> {
+        Ok(Self::new(log4j::parse(str.as_ref())?))
+    }
+
+    /// Renders the pattern without an event, e.g. for file headers and footers.
+    ///
+    /// `$text`, `$datetime`, `$elapsed` and `$mdc` are rendered, placeholders that need an
+    /// event are left out.
+    pub fn render_standalone(&self, clock: &dyn Clock) -> String {
+        // rare enough to not need the pooled buffer
+        let mut buf = String::new();
+        for item in self.items() {
+            match item {
+                PatternItem::Text(v) => {
+                    buf.push_str(v);
+                }
+                PatternItem::Placeholder(placeholder) => {
+                    let inner: Option<Cow<str>> = match placeholder.ty {
+                        PlaceholderType::Text => placeholder.str("value").map(Cow::Borrowed),
+                        PlaceholderType::DateTime => {
+                            Some(Cow::Owned(format_datetime(clock.now(), placeholder)))
+                        }
+                        PlaceholderType::Elapsed => {
+                            Some(Cow::Owned(format_elapsed(clock, placeholder)))
+                        }
+                        PlaceholderType::Mdc => mdc_value(placeholder),
+                        _ => None,
+                    };
+
+                    if let Some(value) = inner {
+                        self.write_value(&mut buf, placeholder, value);
+                    }
+                }
+            }
+        }
+
+        buf
+    }
+
+    /// Writes a placeholder's value with its prefix, suffix, width and escaping applied.
+    fn write_value(&self, buf: &mut String, placeholder: &Placeholder, value: Cow<str>) {
+        let value = if self.escape_control && placeholder.ty.is_user_data() {
+            escape_control(value)
+        } else {
+            value
+        };
+        let value = continuation_lines(value, placeholder);
+
+        if let Some(prefix) = placeholder.str("prefix") {
+            let _ = write!(buf, "{}", prefix);
+        }
+
+        let width = placeholder.int("width").map(|i| i as usize);
+        let is_left_align = placeholder.str("alignment").and_then(|i| {
+            if i.eq_ignore_ascii_case("<") {
+                Some(true)
+            } else if i.eq_ignore_ascii_case(">") {
+                Some(false)
+            } else {
+                None
+            }
+        });
+
+        let value = match placeholder.int("max_width") {
+            Some(max) => truncate(value, max.max(0) as usize),
+            None => value,
+        };
+
+        let (width, is_left_align) = if placeholder.flag("adaptive") {
+            let width = placeholder.adaptive_width(width.unwrap_or(0), value.width());
+            (Some(width), is_left_align.or(Some(true)))
+        } else {
+            (width, is_left_align)
+        };
+
+        // padding goes by terminal columns, wide characters take two
+        let padding = width.map(|i| i.saturating_sub(value.width()));
+        let _ = match (padding, is_left_align) {
+            (Some(padding), Some(true)) => {
+                write!(buf, "{}{:padding$}", value, "", padding = padding)
+            }
+            (Some(padding), Some(false)) => {
+                write!(buf, "{:padding$}{}", "", value, padding = padding)
+            }
+            _ => write!(buf, "{}", value),
+        };
+
+        if let Some(suffix) = placeholder.str("suffix") {
+            let _ = write!(buf, "{}", suffix);
+        }
+    }
+
     pub fn items(&self) -> &[PatternItem] {
         &self.items
     }
@@ -77,28 +189,11 @@ impl<S> EventRenderer<S> for Pattern
 where
     S: Subscriber + for<'l> LookupSpan<'l>,
 {
-    fn render(&self, event: &Event, context: &Context<'_, S>) -> Option<String> {
-        thread_local! {
-            static BUF: RefCell<String> = RefCell::new(String::new())
-        }
-
-        let v = BUF.with(|buf| {
-            let mut buf = buf.borrow_mut();
+    fn render(&self, ctx: &EventContext<'_, S>) -> Option<String> {
+        let event = ctx.event();
 
+        let v = buffer::with_buffer(|buf| {
             for item in self.items() {
-                let parent_span = Lazy::new(|| {
-                    event
-                        .parent()
-                        .and_then(|i| context.span(i))
-                        .or_else(|| context.lookup_current())
-                });
-
-                let fields = Lazy::new(|| {
-                    let mut fields = FieldsVisitor::default();
-                    event.record(&mut fields);
-                    fields
-                });
-
                 match item {
                     PatternItem::Text(v) => {
                         let _ = write!(buf, "{}", v);
@@ -109,15 +204,13 @@ where
                             PlaceholderType::Target => {
                                 Some(Cow::Borrowed(event.metadata().target()))
                             }
-                            PlaceholderType::Level => {
-                                Some(Cow::Borrowed(event.metadata().level().as_str()))
-                            }
+                            PlaceholderType::Level => Some(Cow::Borrowed(ctx.level().as_str())),
                             PlaceholderType::File => event.metadata().file().map(Cow::Borrowed),
                             PlaceholderType::Line => {
                                 event.metadata().line().map(|i| Cow::Owned(i.to_string()))
                             }
                             PlaceholderType::Span => {
-                                let v = parent_span.as_ref().map(|i| {
+                                let v = ctx.parent_span().map(|i| {
                                     let name = i.metadata().name();
                                     let extensions = i.extensions();
                                     let fields = extensions.get::<FieldsVisitor>();
@@ -149,74 +242,166 @@ where
                                     None
                                 }
                             }
-                            PlaceholderType::Message => Some(Cow::Borrowed(fields.message())),
+                            PlaceholderType::Message => Some(Cow::Borrowed(ctx.fields().message())),
                             PlaceholderType::Fields => {
-                                if fields.has_values() {
-                                    Some(Cow::Owned(fields.format_values()))
+                                if ctx.fields().has_values() {
+                                    Some(Cow::Owned(ctx.fields().format_values()))
                                 } else {
                                     None
                                 }
                             }
                             PlaceholderType::DateTime => {
-                                let now = Local::now();
-                                let now = if let Some(fmt) = placeholder.str("fmt") {
-                                    now.format(fmt)
-                                } else {
-                                    now.format("%Y-%m-%d %H:%M:%S%.6f")
-                                };
-
-                                Some(Cow::Owned(now.to_string()))
+                                Some(Cow::Owned(format_datetime(ctx.now(), placeholder)))
                             }
-                        };
-
-                        if let Some(value) = inner {
-                            if let Some(prefix) = placeholder.str("prefix") {
-                                let _ = write!(buf, "{}", prefix);
+                            PlaceholderType::Elapsed => {
+                                Some(Cow::Owned(format_elapsed(ctx.clock(), placeholder)))
                             }
-
-                            let width = placeholder.int("width").map(|i| i as usize);
-                            let is_left_align = placeholder.str("alignment").and_then(|i| {
-                                if i.eq_ignore_ascii_case("<") {
-                                    Some(true)
-                                } else if i.eq_ignore_ascii_case(">") {
-                                    Some(false)
+                            PlaceholderType::Mdc => mdc_value(placeholder),
+                            PlaceholderType::Field => placeholder
+                                .str("name")
+                                .and_then(|name| ctx.field(name))
+                                .map(Cow::Owned),
+                            PlaceholderType::Thread => {
+                                let thread = std::thread::current();
+                                match thread.name() {
+                                    Some(name) => Some(Cow::Owned(name.to_string())),
+                                    None => Some(Cow::Owned(format!("{:?}", thread.id()))),
+                                }
+                            }
+                            PlaceholderType::Backtrace => {
+                                let gate = placeholder
+                                    .str("level")
+                                    .and_then(|i| i.parse::<Level>().ok())
+                                    .unwrap_or(Level::ERROR);
+
+                                if *ctx.level() <= gate {
+                                    let limit = placeholder.int("limit").map(|i| i.max(0) as usize);
+                                    crate::backtrace::capture(placeholder.flag("full"), limit)
+                                        .map(Cow::Owned)
                                 } else {
                                     None
                                 }
-                            });
-
-                            if width.is_some() {
-                                let _ = match is_left_align {
-                                    Some(true) => {
-                                        write!(buf, "{:<width$}", value, width = width.unwrap())
-                                    }
-                                    Some(false) => {
-                                        write!(buf, "{:>width$}", value, width = width.unwrap())
-                                    }
-                                    None => write!(buf, "{}", value),
-                                };
-                            } else {
-                                let _ = write!(buf, "{}", value);
                             }
-
-                            if let Some(suffix) = placeholder.str("suffix") {
-                                let _ = write!(buf, "{}", suffix);
+                            #[cfg(feature = "opentelemetry")]
+                            PlaceholderType::OtelTraceId => ctx
+                                .parent_span()
+                                .and_then(crate::otel::trace_id)
+                                .map(|i| Cow::Owned(i.to_string())),
+                            #[cfg(feature = "opentelemetry")]
+                            PlaceholderType::OtelSpanId => ctx
+                                .parent_span()
+                                .and_then(crate::otel::span_id)
+                                .map(|i| Cow::Owned(i.to_string())),
+                            #[cfg(feature = "opentelemetry")]
+                            PlaceholderType::TraceParent => ctx
+                                .parent_span()
+                                .and_then(crate::otel::traceparent)
+                                .map(Cow::Owned),
+                            #[cfg(feature = "tokio")]
+                            PlaceholderType::TaskId => {
+                                tokio::task::try_id().map(|i| Cow::Owned(i.to_string()))
                             }
+                        };
+
+                        if let Some(value) = inner {
+                            self.write_value(buf, placeholder, value);
                         }
                     }
                 }
             }
 
-            let ret = buf.to_string();
-            buf.clear();
-
-            ret
+            buf.to_string()
         });
 
         Some(v)
     }
 }
 
+fn format_datetime(now: DateTime<Local>, placeholder: &Placeholder) -> String {
+    let now = if let Some(fmt) = placeholder.str("fmt") {
+        now.format(fmt)
+    } else {
+        now.format("%Y-%m-%d %H:%M:%S%.6f")
+    };
+
+    now.to_string()
+}
+
+fn format_elapsed(clock: &dyn Clock, placeholder: &Placeholder) -> String {
+    let elapsed = clock.elapsed();
+    match placeholder.str("unit") {
+        Some("ms") => elapsed.as_millis().to_string(),
+        _ => format!("{:.3}", elapsed.as_secs_f64()),
+    }
+}
+
+fn mdc_value(placeholder: &Placeholder) -> Option<Cow<'static, str>> {
+    match placeholder.str("name") {
+        Some(name) => mdc::get(name).map(Cow::Owned),
+        None if !mdc::is_empty() => Some(Cow::Owned(mdc::format_values())),
+        None => None,
+    }
+}
+
+/// Cuts `value` down to `max` terminal columns, never inside a grapheme cluster.
+fn truncate(value: Cow<str>, max: usize) -> Cow<str> {
+    if value.width() <= max {
+        return value;
+    }
+
+    let mut width = 0;
+    let mut end = 0;
+    for grapheme in value.graphemes(true) {
+        width += grapheme.width();
+        if width > max {
+            break;
+        }
+
+        end += grapheme.len();
+    }
+
+    match value {
+        Cow::Borrowed(v) => Cow::Borrowed(&v[..end]),
+        Cow::Owned(mut v) => {
+            v.truncate(end);
+            Cow::Owned(v)
+        }
+    }
+}
+
+fn escape_control(value: Cow<str>) -> Cow<str> {
+    if !value.chars().any(|i| i.is_control() && i != '\t') {
+        return value;
+    }
+
+    let mut escaped = String::with_capacity(value.len() + 8);
+    for c in value.chars() {
+        if c.is_control() && c != '\t' {
+            escaped.extend(c.escape_debug());
+        } else {
+            escaped.push(c);
+        }
+    }
+
+    Cow::Owned(escaped)
+}
+
+/// Keeps multi-line values from looking like several records: with `escape_newlines` line
+/// breaks are written as `\n`, with `indent` continuation lines are prefixed by its value.
+fn continuation_lines<'a>(value: Cow<'a, str>, placeholder: &Placeholder) -> Cow<'a, str> {
+    if !value.contains('\n') {
+        return value;
+    }
+
+    if placeholder.flag("escape_newlines") {
+        Cow::Owned(value.replace('\r', "\\r").replace('\n', "\\n"))
+    } else if let Some(indent) = placeholder.str("indent") {
+        Cow::Owned(value.replace('\n', &format!("\n{}", indent)))
+    } else {
+        value
+    }
+}
+
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug)]
 pub enum PatternItem {
@@ -251,6 +436,8 @@ pub struct Placeholder {
     ty: PlaceholderType,
     properties: HashMap<String, PlaceholderValue>,
     flags: Vec<String>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    seen_width: AtomicUsize,
 }
 
 impl Placeholder {
@@ -263,6 +450,7 @@ impl Placeholder {
             ty,
             properties: props,
             flags,
+            seen_width: AtomicUsize::new(0),
         }
     }
 
@@ -317,6 +505,18 @@ impl Placeholder {
     pub fn flag<F: AsRef<str>>(&self, flag: F) -> bool {
         self.flags.iter().any(|i| i == flag.as_ref())
     }
+
+    /// Width of an `adaptive` column: the widest value seen so far, at least `min` and
+    /// at most `adaptive_cap`, so columns line up across events.
+    fn adaptive_width(&self, min: usize, width: usize) -> usize {
+        let cap = self
+            .int("adaptive_cap")
+            .map_or(usize::MAX, |i| i.max(0) as usize);
+        let width = width.min(cap);
+        let seen = self.seen_width.fetch_max(width, Ordering::Relaxed);
+
+        seen.max(width).max(min)
+    }
 }
 
 #[derive(Debug)]
@@ -332,9 +532,30 @@ pub enum PlaceholderType {
     Line = 7,
     Fields = 8,
     DateTime = 9,
+    #[cfg(feature = "opentelemetry")]
+    OtelTraceId = 10,
+    #[cfg(feature = "opentelemetry")]
+    OtelSpanId = 11,
+    #[cfg(feature = "tokio")]
+    TaskId = 12,
+    #[cfg(feature = "opentelemetry")]
+    TraceParent = 13,
+    Elapsed = 14,
+    Mdc = 15,
+    Backtrace = 16,
+    Field = 17,
+    Thread = 18,
 }
 
 impl PlaceholderType {
+    /// Whether the value may come from outside the program, rather than from code locations.
+    fn is_user_data(&self) -> bool {
+        matches!(
+            self,
+            Self::Message | Self::Fields | Self::Field | Self::Span | Self::Mdc
+        )
+    }
+
     pub fn from_str<S: AsRef<str>>(v: S) -> Option<Self> {
         let v = v.as_ref().to_lowercase();
 
@@ -348,7 +569,136 @@ impl PlaceholderType {
             "line" => Some(Self::Line),
             "fields" => Some(Self::Fields),
             "datetime" => Some(Self::DateTime),
+            "elapsed" => Some(Self::Elapsed),
+            "mdc" => Some(Self::Mdc),
+            "backtrace" => Some(Self::Backtrace),
+            "field" => Some(Self::Field),
+            "thread" => Some(Self::Thread),
+            #[cfg(feature = "opentelemetry")]
+            "otel_trace_id" => Some(Self::OtelTraceId),
+            #[cfg(feature = "opentelemetry")]
+            "otel_span_id" => Some(Self::OtelSpanId),
+            #[cfg(feature = "tokio")]
+            "task_id" => Some(Self::TaskId),
+            #[cfg(feature = "opentelemetry")]
+            "traceparent" => Some(Self::TraceParent),
             _ => None,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::pattern::{
+        escape_control, truncate, Pattern, Placeholder, PlaceholderType, PlaceholderValue,
+    };
+    use std::borrow::Cow;
+    use std::collections::HashMap;
+
+    #[test]
+    fn truncates_by_display_width() {
+        assert_eq!(truncate(Cow::Borrowed("hello"), 3), "hel");
+        assert_eq!(truncate(Cow::Borrowed("日本語"), 5), "日本");
+        assert_eq!(truncate(Cow::Borrowed("e\u{301}e\u{301}"), 1), "e\u{301}");
+        assert_eq!(truncate(Cow::Borrowed("👍🏽ok"), 2), "👍🏽");
+    }
+
+    #[test]
+    fn escapes_control_characters() {
+        assert_eq!(escape_control(Cow::Borrowed("a\tb")), "a\tb");
+        assert_eq!(
+            escape_control(Cow::Borrowed("\x1b[31mred\nfake record")),
+            "\\u{1b}[31mred\\nfake record"
+        );
+    }
+
+    #[test]
+    fn adaptive_width_grows_up_to_cap() {
+        let properties =
+            HashMap::from([("adaptive_cap".to_string(), PlaceholderValue::Integer(6))]);
+        let placeholder = Placeholder::new(
+            PlaceholderType::Target,
+            properties,
+            vec!["adaptive".to_string()],
+        );
+        let pattern = Pattern::new(Vec::new());
+
+        let render = |value: &str| {
+            let mut buf = String::new();
+            pattern.write_value(&mut buf, &placeholder, Cow::Borrowed(value));
+            buf
+        };
+
+        assert_eq!(render("ab"), "ab");
+        assert_eq!(render("abcd"), "abcd");
+        assert_eq!(render("ab"), "ab  ");
+        assert_eq!(render("abcdefghij"), "abcdefghij");
+        assert_eq!(render("a"), "a     ");
+    }
+
+    #[test]
+    fn marks_continuation_lines() {
+        let pattern = Pattern::new(Vec::new());
+        let render = |properties: HashMap<String, PlaceholderValue>, flags: &[&str]| {
+            let flags = flags.iter().map(|i| i.to_string()).collect();
+            let placeholder = Placeholder::new(PlaceholderType::Message, properties, flags);
+
+            let mut buf = String::new();
+            let value = Cow::Borrowed("failed:\r\n  at db.rs\n  at main.rs");
+            pattern.write_value(&mut buf, &placeholder, value);
+            buf
+        };
+        let indent = || {
+            HashMap::from([(
+                "indent".to_string(),
+                PlaceholderValue::String("    | ".to_string()),
+            )])
+        };
+
+        assert_eq!(
+            render(HashMap::new(), &[]),
+            "failed:\r\n  at db.rs\n  at main.rs"
+        );
+        assert_eq!(
+            render(indent(), &[]),
+            "failed:\r\n    |   at db.rs\n    |   at main.rs"
+        );
+        assert_eq!(
+            render(indent(), &["escape_newlines"]),
+            "failed:\\r\\n  at db.rs\\n  at main.rs"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "tokio")]
+    fn renders_task_id_inside_tasks() {
+        use crate::pattern::PatternItem;
+        use crate::testing::{CaptureAppender, TestConfig};
+        use crate::ConfigurableLayer;
+        use tracing::info;
+        use tracing_subscriber::layer::SubscriberExt;
+        use tracing_subscriber::registry;
+
+        let capture = CaptureAppender::with_pattern(Pattern::new(vec![PatternItem::Placeholder(
+            Placeholder::new(PlaceholderType::TaskId, HashMap::new(), vec![]),
+        )]));
+        let subscriber = registry().with(ConfigurableLayer::new(TestConfig::new(capture.clone())));
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let task_id = tracing::subscriber::with_default(subscriber, || {
+            info!("outside");
+            runtime.block_on(async {
+                let task = tokio::spawn(async {
+                    info!("inside");
+                    tokio::task::id()
+                });
+                task.await.unwrap()
+            })
+        });
+
+        let rendered: Vec<String> = capture.events().into_iter().map(|i| i.rendered).collect();
+        assert_eq!(rendered, ["".to_string(), task_id.to_string()]);
+    }
+}
@@ -0,0 +1,298 @@
+//! Translation of log4j/logback layout strings, e.g. `%d{HH:mm:ss.SSS} %-5p [%t] %c - %m%n`.
+//!
+//! Supported conversions:
+//!
+//! | log4j                       | pattern                    |
+//! |-----------------------------|----------------------------|
+//! | `%d`, `%date{fmt}`          | `$datetime(fmt = ...)`     |
+//! | `%p`, `%le`, `%level`       | `$level`                   |
+//! | `%c`, `%lo`, `%logger`      | `$target`                  |
+//! | `%t`, `%thread`             | `$thread`                  |
+//! | `%m`, `%msg`, `%message`    | `$message`                 |
+//! | `%F`, `%file`               | `$file`                    |
+//! | `%L`, `%line`               | `$line`                    |
+//! | `%X{key}`, `%mdc{key}`      | `$mdc(name = key)`         |
+//! | `%r`, `%relative`           | `$elapsed(unit = 'ms')`    |
+//! | `%n`                        | newline, dropped at the end since appenders end lines themselves |
+//! | `%%`                        | `%`                        |
+//!
+//! Format modifiers map to `width`, `alignment` and `max_width`. Unlike log4j, values
+//! longer than the maximum width are cut at the end rather than at the beginning.
+//! Options this crate has no equivalent for, like the precision of `%c{1}`, are ignored.
+
+use crate::pattern::{PatternItem, Placeholder, PlaceholderType, PlaceholderValue};
+use anyhow::{anyhow, bail};
+use std::collections::HashMap;
+
+/// Conversion names. A conversion is the whole run of letters after `%` and its modifiers,
+/// so `%method` is rejected rather than read as `%m` followed by `ethod`.
+const CONVERSIONS: &[&str] = &[
+    "relative", "message", "logger", "thread", "level", "date", "file", "line", "msg", "mdc",
+    "MDC", "le", "lo", "d", "p", "c", "t", "m", "F", "L", "X", "r", "n",
+];
+
+pub(crate) fn parse(str: &str) -> Result<Vec<PatternItem>, anyhow::Error> {
+    let mut items = Vec::new();
+    let mut text = String::new();
+    let mut chars = str.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            text.push(c);
+            continue;
+        }
+
+        if chars.next_if_eq(&'%').is_some() {
+            text.push('%');
+            continue;
+        }
+
+        // format modifiers: [-][min][.max]
+        let left_align = chars.next_if_eq(&'-').is_some();
+        let width = read_number(&mut chars, str)?;
+        let max_width = match chars.next_if_eq(&'.') {
+            Some(_) => Some(
+                read_number(&mut chars, str)?
+                    .ok_or_else(|| anyhow!("expected a maximum width after `.` in `{}`", str))?,
+            ),
+            None => None,
+        };
+
+        let mut name = String::new();
+        while let Some(c) = chars.next_if(|i| i.is_ascii_alphabetic()) {
+            name.push(c);
+        }
+
+        let conversion = CONVERSIONS
+            .iter()
+            .find(|i| **i == name)
+            .ok_or_else(|| anyhow!("unsupported conversion `%{}` in `{}`", name, str))?;
+
+        let mut option = None;
+        while chars.next_if_eq(&'{').is_some() {
+            let mut value = String::new();
+            loop {
+                match chars.next() {
+                    Some('}') => break,
+                    Some(c) => value.push(c),
+                    None => bail!("unclosed `{{` after `%{}` in `{}`", conversion, str),
+                }
+            }
+
+            // later options (e.g. the time zone of `%d`) have no equivalent
+            option.get_or_insert(value);
+        }
+
+        if *conversion == "n" {
+            // appenders terminate lines themselves
+            if chars.peek().is_some() {
+                text.push('\n');
+            }
+            continue;
+        }
+
+        let mut properties = HashMap::new();
+        let ty = match *conversion {
+            "d" | "date" => {
+                let fmt = date_format(option.as_deref().unwrap_or("DEFAULT"))?;
+                properties.insert("fmt".to_string(), PlaceholderValue::String(fmt));
+                PlaceholderType::DateTime
+            }
+            "p" | "le" | "level" => PlaceholderType::Level,
+            "c" | "lo" | "logger" => PlaceholderType::Target,
+            "t" | "thread" => PlaceholderType::Thread,
+            "m" | "msg" | "message" => PlaceholderType::Message,
+            "F" | "file" => PlaceholderType::File,
+            "L" | "line" => PlaceholderType::Line,
+            "X" | "mdc" | "MDC" => {
+                if let Some(key) = option {
+                    properties.insert("name".to_string(), PlaceholderValue::String(key));
+                }
+                PlaceholderType::Mdc
+            }
+            "r" | "relative" => {
+                let unit = PlaceholderValue::String("ms".to_string());
+                properties.insert("unit".to_string(), unit);
+                PlaceholderType::Elapsed
+            }
+            _ => unreachable!(),
+        };
+
+        if let Some(width) = width {
+            let alignment = if left_align { "<" } else { ">" };
+            properties.insert("width".to_string(), PlaceholderValue::Integer(width));
+            properties.insert(
+                "alignment".to_string(),
+                PlaceholderValue::String(alignment.to_string()),
+            );
+        }
+
+        if let Some(max_width) = max_width {
+            properties.insert(
+                "max_width".to_string(),
+                PlaceholderValue::Integer(max_width),
+            );
+        }
+
+        if !text.is_empty() {
+            items.push(PatternItem::Text(std::mem::take(&mut text)));
+        }
+        items.push(PatternItem::Placeholder(Placeholder::new(
+            ty,
+            properties,
+            Vec::new(),
+        )));
+    }
+
+    if !text.is_empty() {
+        items.push(PatternItem::Text(text));
+    }
+
+    Ok(items)
+}
+
+fn read_number(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    str: &str,
+) -> Result<Option<i32>, anyhow::Error> {
+    let mut number = None;
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        let digit = digit.to_digit(10).unwrap() as i32;
+        let value = number
+            .unwrap_or(0i32)
+            .checked_mul(10)
+            .and_then(|i| i.checked_add(digit))
+            .ok_or_else(|| anyhow!("width out of range in `{}`", str))?;
+        number = Some(value);
+    }
+
+    Ok(number)
+}
+
+/// Converts a `SimpleDateFormat`/`DateTimeFormatter` style format, or one of log4j's
+/// named formats, into a chrono format string.
+fn date_format(fmt: &str) -> Result<String, anyhow::Error> {
+    let fmt = match fmt {
+        "DEFAULT" => "yyyy-MM-dd HH:mm:ss,SSS",
+        "ISO8601" => "yyyy-MM-dd'T'HH:mm:ss,SSS",
+        "ISO8601_BASIC" => "yyyyMMdd'T'HHmmss,SSS",
+        "ABSOLUTE" => "HH:mm:ss,SSS",
+        "DATE" => "dd MMM yyyy HH:mm:ss,SSS",
+        "COMPACT" => "yyyyMMddHHmmssSSS",
+        "UNIX" => return Ok("%s".to_string()),
+        fmt => fmt,
+    };
+
+    let mut out = String::new();
+    let mut chars = fmt.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\'' {
+            // quoted literal, `''` is a single quote
+            if chars.next_if_eq(&'\'').is_some() {
+                out.push('\'');
+                continue;
+            }
+
+            loop {
+                match chars.next() {
+                    Some('\'') if chars.next_if_eq(&'\'').is_some() => out.push('\''),
+                    Some('\'') => break,
+                    Some('%') => out.push_str("%%"),
+                    Some(c) => out.push(c),
+                    None => bail!("unclosed quote in date format `{}`", fmt),
+                }
+            }
+            continue;
+        }
+
+        if !c.is_ascii_alphabetic() {
+            match c {
+                '%' => out.push_str("%%"),
+                c => out.push(c),
+            }
+            continue;
+        }
+
+        let mut count = 1;
+        while chars.next_if_eq(&c).is_some() {
+            count += 1;
+        }
+
+        let spec = match (c, count) {
+            ('y' | 'u', 2) => "%y",
+            ('y' | 'u', _) => "%Y",
+            ('M', 1) => "%-m",
+            ('M', 2) => "%m",
+            ('M', 3) => "%b",
+            ('M', _) => "%B",
+            ('d', 1) => "%-d",
+            ('d', _) => "%d",
+            ('D', _) => "%j",
+            ('H', 1) => "%-H",
+            ('H', _) => "%H",
+            ('h', 1) => "%-I",
+            ('h', _) => "%I",
+            ('m', _) => "%M",
+            ('s', _) => "%S",
+            ('S', 1..=3) => "%3f",
+            ('S', 4..=6) => "%6f",
+            ('S', _) => "%9f",
+            ('a', _) => "%p",
+            ('E', 1..=3) => "%a",
+            ('E', _) => "%A",
+            ('z', _) => "%Z",
+            ('Z', _) => "%z",
+            ('X' | 'x', _) => "%:z",
+            _ => bail!("unsupported date format letter `{}` in `{}`", c, fmt),
+        };
+        out.push_str(spec);
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::pattern::log4j::{date_format, parse};
+    use crate::pattern::{PatternItem, PlaceholderType};
+
+    #[test]
+    fn translates_layout() {
+        let items = parse("%d{HH:mm:ss.SSS} %-5p [%t] %c{1} - %msg%n").unwrap();
+        let placeholder = |idx: usize| match &items[idx] {
+            PatternItem::Placeholder(i) => i,
+            i => panic!("expected placeholder, got {:?}", i),
+        };
+
+        assert_eq!(items.len(), 9);
+        assert_eq!(placeholder(0).str("fmt"), Some("%H:%M:%S.%3f"));
+        assert!(matches!(placeholder(2).ty(), PlaceholderType::Level));
+        assert_eq!(placeholder(2).int("width"), Some(5));
+        assert_eq!(placeholder(2).str("alignment"), Some("<"));
+        assert!(matches!(placeholder(4).ty(), PlaceholderType::Thread));
+        assert!(matches!(placeholder(6).ty(), PlaceholderType::Target));
+        assert!(matches!(placeholder(8).ty(), PlaceholderType::Message));
+        assert!(parse("%q").is_err());
+    }
+
+    #[test]
+    fn rejects_unsupported_conversions() {
+        for layout in ["%throwable", "%method", "%marker", "%m%nopex", "%msgs"] {
+            let error = parse(layout).unwrap_err().to_string();
+            assert!(error.contains("unsupported conversion"), "{}", error);
+        }
+
+        assert!(parse("%99999999999m").is_err());
+        assert!(parse("%.99999999999m").is_err());
+        assert_eq!(parse("%m%n").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn translates_date_formats() {
+        assert_eq!(date_format("DEFAULT").unwrap(), "%Y-%m-%d %H:%M:%S,%3f");
+        assert_eq!(date_format("yyyy-MM-dd'T'HH:mm").unwrap(), "%Y-%m-%dT%H:%M");
+        assert_eq!(date_format("d MMM yy, h a").unwrap(), "%-d %b %y, %-I %p");
+        assert!(date_format("Q").is_err());
+    }
+}
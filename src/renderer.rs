@@ -1,6 +1,378 @@
-use tracing::{Event, Subscriber};
+use crate::appender::{Appender, Record, ResolvedFields};
+use crate::clock::Clock;
+use crate::fields::{EventValue, FieldsVisitor, PooledFields};
+use crate::resolver::Resolvers;
+use chrono::{DateTime, Local};
+use once_cell::unsync::OnceCell;
+use std::cell::RefCell;
+use tracing::{Event, Level, Subscriber};
 use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::{LookupSpan, SpanRef};
 
-pub trait EventRenderer<S: Subscriber> {
-    fn render(&self, event: &Event, context: &Context<'_, S>) -> Option<String>;
+/// Event view shared by all renderers and appenders of a single event.
+///
+/// Fields are visited, the parent span is looked up and the clock is read at most once,
+/// on first use.
+pub struct EventContext<'a, S>
+where
+    S: Subscriber + for<'l> LookupSpan<'l>,
+{
+    event: &'a Event<'a>,
+    context: &'a Context<'a, S>,
+    clock: &'a dyn Clock,
+    level: Level,
+    static_fields: &'a [(&'static str, EventValue)],
+    resolvers: Option<&'a Resolvers>,
+    // values of the resolvers asked for so far, by position
+    resolved: RefCell<Vec<Option<Option<EventValue>>>>,
+    now: OnceCell<DateTime<Local>>,
+    fields: OnceCell<PooledFields>,
+    parent_span: OnceCell<Option<SpanRef<'a, S>>>,
+}
+
+impl<'a, S> EventContext<'a, S>
+where
+    S: Subscriber + for<'l> LookupSpan<'l>,
+{
+    pub fn new(event: &'a Event<'a>, context: &'a Context<'a, S>, clock: &'a dyn Clock) -> Self {
+        Self {
+            event,
+            context,
+            clock,
+            level: *event.metadata().level(),
+            static_fields: &[],
+            resolvers: None,
+            resolved: RefCell::new(Vec::new()),
+            now: OnceCell::new(),
+            fields: OnceCell::new(),
+            parent_span: OnceCell::new(),
+        }
+    }
+
+    /// Renders the event with `level` instead of its own, see
+    /// [`LayerConfig::remap_level`](crate::config::LayerConfig::remap_level).
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Fields added to every event, unless the event records a field with the same name.
+    pub fn with_static_fields(mut self, fields: &'a [(&'static str, EventValue)]) -> Self {
+        self.static_fields = fields;
+        self
+    }
+
+    pub fn with_resolvers(mut self, resolvers: &'a Resolvers) -> Self {
+        self.resolvers = Some(resolvers).filter(|i| !i.is_empty());
+        self
+    }
+
+    pub fn event(&self) -> &'a Event<'a> {
+        self.event
+    }
+
+    pub fn level(&self) -> &Level {
+        &self.level
+    }
+
+    pub fn context(&self) -> &'a Context<'a, S> {
+        self.context
+    }
+
+    pub fn clock(&self) -> &'a dyn Clock {
+        self.clock
+    }
+
+    /// Event timestamp, the same for every renderer.
+    pub fn now(&self) -> DateTime<Local> {
+        *self.now.get_or_init(|| self.clock.now())
+    }
+
+    pub fn fields(&self) -> &FieldsVisitor {
+        self.fields.get_or_init(|| {
+            let mut fields = FieldsVisitor::pooled();
+            self.event.record(&mut *fields);
+            for (name, value) in self.static_fields {
+                fields.insert_if_absent(name, value.clone());
+            }
+            fields
+        })
+    }
+
+    /// Value of the event field `name`, or of the resolver registered as `name`.
+    ///
+    /// Multiple values of a field are joined with `,`.
+    pub fn field(&self, name: &str) -> Option<String> {
+        if let Some((_, values)) = self.fields().values().find(|(i, _)| *i == name) {
+            return Some(
+                values
+                    .iter()
+                    .map(|i| i.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            );
+        }
+
+        let idx = self.resolvers?.position(name)?;
+        self.resolve(idx).map(|i| i.to_string())
+    }
+
+    /// Value of the resolver at `idx`, running it on first use.
+    fn resolve(&self, idx: usize) -> Option<EventValue> {
+        let resolvers = self.resolvers?;
+
+        let mut resolved = self.resolved.borrow_mut();
+        if resolved.is_empty() {
+            resolved.resize(resolvers.len(), None);
+        }
+
+        resolved[idx]
+            .get_or_insert_with(|| resolvers.resolve(idx, self.event.metadata()))
+            .clone()
+    }
+
+    pub fn parent_span(&self) -> Option<&SpanRef<'a, S>> {
+        self.parent_span
+            .get_or_init(|| {
+                self.event
+                    .parent()
+                    .and_then(|i| self.context.span(i))
+                    .or_else(|| self.context.lookup_current())
+            })
+            .as_ref()
+    }
+
+    pub fn record(&self) -> Record<'_> {
+        Record::new(self.event.metadata(), self.fields())
+            .with_level(self.level)
+            .with_time(self.now())
+            .with_resolvers(self)
+    }
+}
+
+impl<S> ResolvedFields for EventContext<'_, S>
+where
+    S: Subscriber + for<'l> LookupSpan<'l>,
+{
+    fn resolved_fields(&self) -> Vec<(&'static str, EventValue)> {
+        let Some(resolvers) = self.resolvers else {
+            return Vec::new();
+        };
+
+        let fields = self.fields();
+        (0..resolvers.len())
+            .filter(|idx| {
+                !fields
+                    .values()
+                    .any(|(name, _)| name == resolvers.name(*idx))
+            })
+            .filter_map(|idx| Some((resolvers.name(idx), self.resolve(idx)?)))
+            .collect()
+    }
+}
+
+pub trait EventRenderer<S>
+where
+    S: Subscriber + for<'l> LookupSpan<'l>,
+{
+    fn render(&self, event: &EventContext<'_, S>) -> Option<String>;
+}
+
+/// Lines rendered for the appenders of an event, appenders sharing a pattern share the line.
+pub(crate) struct RenderedLines {
+    lines: Vec<Option<String>>,
+    // position in `lines` for each appender
+    index: Vec<usize>,
+}
+
+impl RenderedLines {
+    pub(crate) fn render<S>(appenders: &[Box<dyn Appender>], event: &EventContext<'_, S>) -> Self
+    where
+        S: Subscriber + for<'l> LookupSpan<'l>,
+    {
+        let mut patterns = Vec::with_capacity(appenders.len());
+        let mut lines = Vec::with_capacity(appenders.len());
+        let mut index = Vec::with_capacity(appenders.len());
+
+        let target = event.event().metadata().target();
+        for appender in appenders {
+            let pattern = appender.pattern_for(target);
+            let idx = match patterns.iter().position(|i| std::ptr::eq(*i, pattern)) {
+                Some(idx) => idx,
+                None => {
+                    patterns.push(pattern);
+                    lines.push(pattern.render(event));
+                    lines.len() - 1
+                }
+            };
+
+            index.push(idx);
+        }
+
+        Self { lines, index }
+    }
+
+    /// The line for the appender at `appender`, `None` if its pattern rendered nothing.
+    pub(crate) fn get(&self, appender: usize) -> Option<&str> {
+        self.index
+            .get(appender)
+            .and_then(|i| self.lines[*i].as_deref())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::appender::Appender;
+    use crate::clock::{self, Clock};
+    use crate::pattern::{Pattern, PatternItem, Placeholder, PlaceholderType};
+    use crate::renderer::{EventContext, RenderedLines};
+    use crate::testing::{CaptureAppender, TestConfig};
+    use crate::ConfigurableLayer;
+    use chrono::{DateTime, Local, TimeZone};
+    use std::collections::HashMap;
+    use std::fmt::{Debug, Formatter};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tracing::{info, Event, Subscriber};
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+    use tracing_subscriber::registry;
+    use tracing_subscriber::registry::LookupSpan;
+
+    fn placeholder(kind: PlaceholderType) -> PatternItem {
+        PatternItem::Placeholder(Placeholder::new(kind, HashMap::new(), vec![]))
+    }
+
+    /// Counts how often it's formatted, i.e. how often the event fields are visited.
+    struct Probe(Arc<AtomicUsize>);
+
+    impl Debug for Probe {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            write!(f, "probe")
+        }
+    }
+
+    #[derive(Default)]
+    struct CountingClock(AtomicUsize);
+
+    impl Clock for CountingClock {
+        fn now(&self) -> DateTime<Local> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Local.with_ymd_and_hms(2024, 3, 1, 8, 30, 0).unwrap()
+        }
+
+        fn elapsed(&self) -> Duration {
+            Duration::ZERO
+        }
+    }
+
+    #[test]
+    fn visits_fields_and_reads_clock_once_per_event() {
+        let console = CaptureAppender::with_pattern(Pattern::new(vec![
+            placeholder(PlaceholderType::Message),
+            PatternItem::Text(" ".to_string()),
+            placeholder(PlaceholderType::Fields),
+        ]));
+        let file = CaptureAppender::with_pattern(Pattern::new(vec![
+            placeholder(PlaceholderType::DateTime),
+            PatternItem::Text(" ".to_string()),
+            placeholder(PlaceholderType::Fields),
+        ]));
+        let json = CaptureAppender::with_pattern(Pattern::new(vec![placeholder(
+            PlaceholderType::DateTime,
+        )]));
+
+        let clock = Arc::new(CountingClock::default());
+        let config = TestConfig::new(console.clone())
+            .with_appender(file.clone())
+            .with_appender(json.clone());
+        let layer = ConfigurableLayer::new(config).with_clock(clock.clone());
+
+        let visits = Arc::new(AtomicUsize::new(0));
+        tracing::subscriber::with_default(registry().with(layer), || {
+            info!(probe = ?Probe(visits.clone()), "hello");
+        });
+
+        assert_eq!(console.events()[0].rendered, "hello probe=`probe`");
+        assert_eq!(
+            file.events()[0].rendered,
+            format!("{} probe=`probe`", json.events()[0].rendered)
+        );
+        assert_eq!(visits.load(Ordering::Relaxed), 1);
+        assert_eq!(clock.0.load(Ordering::Relaxed), 1);
+    }
+
+    /// Renders each event for `appenders` and keeps the result.
+    struct RenderProbe {
+        appenders: Vec<CaptureAppender>,
+        rendered: Arc<Mutex<Vec<RenderedLines>>>,
+    }
+
+    impl<S: Subscriber + for<'l> LookupSpan<'l>> Layer<S> for RenderProbe {
+        fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+            let clock = clock::system();
+            let event = EventContext::new(event, &ctx, &*clock);
+            let appenders = self
+                .appenders
+                .iter()
+                .map(|i| Box::new(i.clone()) as Box<dyn Appender>)
+                .collect::<Vec<_>>();
+
+            let lines = RenderedLines::render(&appenders, &event);
+            self.rendered.lock().unwrap().push(lines);
+        }
+    }
+
+    #[test]
+    fn renders_shared_patterns_once() {
+        let console = CaptureAppender::with_pattern(Pattern::new(vec![placeholder(
+            PlaceholderType::Message,
+        )]));
+        let level =
+            CaptureAppender::with_pattern(Pattern::new(vec![placeholder(PlaceholderType::Level)]));
+
+        let rendered = Arc::new(Mutex::new(Vec::new()));
+        let probe = RenderProbe {
+            // clones share their pattern
+            appenders: vec![console.clone(), level, console],
+            rendered: rendered.clone(),
+        };
+
+        tracing::subscriber::with_default(registry().with(probe), || {
+            info!("hello");
+        });
+
+        let lines = &rendered.lock().unwrap()[0];
+        assert_eq!(lines.lines.len(), 2);
+        assert_eq!(
+            (0..3).map(|i| lines.get(i)).collect::<Vec<_>>(),
+            [Some("hello"), Some("INFO"), Some("hello")]
+        );
+    }
+
+    #[test]
+    fn adds_static_and_env_fields() {
+        std::env::set_var("TRACING_CONFIGURABLE_TEST_POD", "checkout-7d9f");
+        std::env::remove_var("TRACING_CONFIGURABLE_TEST_UNSET");
+
+        let capture = CaptureAppender::new();
+        let layer = ConfigurableLayer::new(TestConfig::new(capture.clone()))
+            .with_static_field("service", "checkout")
+            .with_static_field("version", "1.4.2")
+            .with_env_field("pod", "TRACING_CONFIGURABLE_TEST_POD")
+            .with_env_field("region", "TRACING_CONFIGURABLE_TEST_UNSET");
+
+        tracing::subscriber::with_default(registry().with(layer), || {
+            info!(version = "2.0.0-canary", "deployed");
+        });
+
+        let event = &capture.events()[0];
+        assert_eq!(event.field("service"), Some("checkout"));
+        assert_eq!(event.field("pod"), Some("checkout-7d9f"));
+        assert_eq!(event.field("region"), None);
+        // the event's own field wins
+        assert_eq!(event.field("version"), Some("2.0.0-canary"));
+        assert_eq!(event.fields.len(), 3);
+    }
 }
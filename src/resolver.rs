@@ -0,0 +1,95 @@
+use crate::fields::EventValue;
+use tracing::Metadata;
+
+type Resolver = Box<dyn Fn(&'static Metadata<'static>) -> Option<EventValue> + Send + Sync>;
+
+/// Named values computed per event, e.g. the current request count or memory usage.
+///
+/// A resolver only runs when a renderer asks for its value, at most once per event.
+#[derive(Default)]
+pub struct Resolvers {
+    resolvers: Vec<(&'static str, Resolver)>,
+}
+
+impl Resolvers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `resolver` as `name`, replacing a resolver with the same name.
+    pub fn register<F>(&mut self, name: &'static str, resolver: F)
+    where
+        F: Fn(&'static Metadata<'static>) -> Option<EventValue> + Send + Sync + 'static,
+    {
+        self.resolvers.retain(|(i, _)| *i != name);
+        self.resolvers.push((name, Box::new(resolver)));
+    }
+
+    pub(crate) fn name(&self, idx: usize) -> &'static str {
+        self.resolvers[idx].0
+    }
+
+    pub(crate) fn position(&self, name: &str) -> Option<usize> {
+        self.resolvers.iter().position(|(i, _)| *i == name)
+    }
+
+    pub(crate) fn resolve(
+        &self,
+        idx: usize,
+        metadata: &'static Metadata<'static>,
+    ) -> Option<EventValue> {
+        (self.resolvers[idx].1)(metadata)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resolvers.is_empty()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.resolvers.len()
+    }
+}
+
+#[cfg(all(test, feature = "parse"))]
+mod test {
+    use crate::fields::EventValue;
+    use crate::pattern::Pattern;
+    use crate::testing::{CaptureAppender, TestConfig};
+    use crate::ConfigurableLayer;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    use tracing::info;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::registry;
+
+    #[test]
+    fn resolves_only_referenced_values() {
+        let calls = Arc::new(AtomicU64::new(0));
+        let unused = Arc::new(AtomicU64::new(0));
+
+        let capture = CaptureAppender::with_pattern(
+            Pattern::try_parse("$message $field(name = 'requests') $field(name = 'user')").unwrap(),
+        );
+        let layer = ConfigurableLayer::new(TestConfig::new(capture.clone()))
+            .with_resolver("requests", {
+                let calls = calls.clone();
+                move |_| Some(EventValue::U64(calls.fetch_add(1, Ordering::Relaxed) + 1))
+            })
+            .with_resolver("memory", {
+                let unused = unused.clone();
+                move |_| {
+                    unused.fetch_add(1, Ordering::Relaxed);
+                    None
+                }
+            });
+
+        tracing::subscriber::with_default(registry().with(layer), || {
+            info!(user = "alice", "first");
+            info!(user = "bob", "second");
+        });
+
+        let rendered: Vec<String> = capture.events().into_iter().map(|i| i.rendered).collect();
+        assert_eq!(rendered, ["first 1 alice", "second 2 bob"]);
+        assert_eq!(unused.load(Ordering::Relaxed), 0);
+    }
+}
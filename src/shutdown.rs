@@ -0,0 +1,173 @@
+use crate::config::LayerConfig;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+pub(crate) struct ShutdownState {
+    stopped: AtomicBool,
+    in_flight: AtomicUsize,
+    // signalled when the last event in flight after the shutdown is done
+    idle: Mutex<()>,
+    done: Condvar,
+}
+
+impl ShutdownState {
+    pub(crate) fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+
+    /// Marks an event as being written, unless the layer was shut down.
+    pub(crate) fn enter(&self) -> Option<InFlight<'_>> {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+
+        // re-check after announcing ourselves, `shutdown` may have missed us otherwise
+        if self.is_stopped() {
+            self.leave();
+            None
+        } else {
+            Some(InFlight(self))
+        }
+    }
+
+    fn leave(&self) {
+        if self.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 && self.is_stopped() {
+            // under the lock, so `shutdown` can't miss it between its check and its wait
+            let _idle = self.idle.lock().unwrap();
+            self.done.notify_all();
+        }
+    }
+
+    /// Waits until no event is being written, or `deadline` passed.
+    fn wait_idle(&self, deadline: Instant) {
+        let mut idle = self.idle.lock().unwrap();
+
+        while self.in_flight.load(Ordering::SeqCst) > 0 {
+            let now = Instant::now();
+            if now >= deadline {
+                return;
+            }
+
+            idle = self.done.wait_timeout(idle, deadline - now).unwrap().0;
+        }
+    }
+}
+
+pub(crate) struct InFlight<'a>(&'a ShutdownState);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.leave();
+    }
+}
+
+/// Stops a [`ConfigurableLayer`](crate::ConfigurableLayer) and lets its config close the appenders.
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    config: Arc<dyn LayerConfig>,
+    state: Arc<ShutdownState>,
+}
+
+impl ShutdownHandle {
+    pub(crate) fn new(config: Arc<dyn LayerConfig>, state: Arc<ShutdownState>) -> Self {
+        Self { config, state }
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.state.is_stopped()
+    }
+
+    /// Stops accepting events, waits for events already being written and then calls
+    /// [`LayerConfig::shutdown`] to drain, flush and close the appenders, all within `timeout`.
+    ///
+    /// Only the first call has any effect.
+    pub fn shutdown(&self, timeout: Duration) {
+        if self.state.stopped.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let deadline = Instant::now() + timeout;
+        self.state.wait_idle(deadline);
+        self.config.shutdown(deadline);
+    }
+
+    /// Shuts the layer down on SIGINT/SIGTERM, then lets the signal take its default action,
+    /// so the process still ends the way it would without the hook.
+    ///
+    /// The shutdown runs on a thread of its own rather than in the signal handler.
+    #[cfg(all(unix, feature = "signal"))]
+    pub fn install_signal_hook(&self, timeout: Duration) -> std::io::Result<()> {
+        use crate::diagnostics::{self, Diagnostic};
+        use signal_hook::consts::{SIGINT, SIGTERM};
+        use signal_hook::iterator::Signals;
+
+        let mut signals = Signals::new([SIGINT, SIGTERM])?;
+        let handle = self.clone();
+
+        std::thread::Builder::new()
+            .name("tracing-configurable-signals".to_string())
+            .spawn(move || {
+                if let Some(signal) = signals.forever().next() {
+                    handle.shutdown(timeout);
+                    if let Err(error) = signal_hook::low_level::emulate_default_handler(signal) {
+                        diagnostics::report(Diagnostic::SignalError { error: &error });
+                    }
+                }
+            })?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::appender::file::{FileAppender, FileOptions};
+    use crate::appender::non_blocking::NonBlockingAppender;
+    use crate::pattern::{Pattern, PatternItem, Placeholder, PlaceholderType};
+    use crate::testing::TestConfig;
+    use crate::ConfigurableLayer;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tracing::info;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::registry;
+
+    #[test]
+    fn closes_appenders_of_running_layer() {
+        let path = std::env::temp_dir().join(format!(
+            "tracing-configurable-shutdown-{}.log",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let options = FileOptions::new().footer(Pattern::new(vec![PatternItem::Text(
+            "# closed".to_string(),
+        )]));
+        let file = FileAppender::with_options(
+            Pattern::new(vec![PatternItem::Placeholder(Placeholder::new(
+                PlaceholderType::Message,
+                HashMap::new(),
+                vec![],
+            ))]),
+            &path,
+            options,
+        )
+        .unwrap();
+        let appender = Arc::new(NonBlockingAppender::new(file, 16));
+
+        let layer = ConfigurableLayer::new(TestConfig::new(appender.clone()));
+        let handle = layer.shutdown_handle();
+        let _subscriber = tracing::subscriber::set_default(registry().with(layer));
+
+        info!("first");
+        info!("second");
+        handle.shutdown(Duration::from_secs(5));
+        info!("after shutdown");
+
+        // the layer and the appender are still alive, the queue was written by the shutdown
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(content, "first\nsecond\n# closed\n");
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use tracing::Level;
+
+/// Logging health counters maintained by the layer.
+#[derive(Default)]
+pub struct Telemetry {
+    levels: [AtomicU64; 5],
+    targets: RwLock<HashMap<String, AtomicU64>>,
+    dropped: AtomicU64,
+    overflowed: AtomicU64,
+    appender_errors: AtomicU64,
+}
+
+impl Telemetry {
+    pub(crate) fn record_event(&self, level: &Level, target: &str) {
+        self.levels[level_index(level)].fetch_add(1, Ordering::Relaxed);
+
+        if let Some(counter) = self.targets.read().unwrap().get(target) {
+            counter.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        self.targets
+            .write()
+            .unwrap()
+            .entry(target.to_string())
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_overflow(&self) {
+        self.overflowed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_appender_error(&self) {
+        self.appender_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Takes a point-in-time copy of the counters, keeping only the `top_targets` busiest targets.
+    pub fn snapshot(&self, top_targets: usize) -> TelemetrySnapshot {
+        let mut targets = self
+            .targets
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(target, count)| (target.clone(), count.load(Ordering::Relaxed)))
+            .collect::<Vec<_>>();
+
+        targets.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        targets.truncate(top_targets);
+
+        TelemetrySnapshot {
+            trace: self.levels[0].load(Ordering::Relaxed),
+            debug: self.levels[1].load(Ordering::Relaxed),
+            info: self.levels[2].load(Ordering::Relaxed),
+            warn: self.levels[3].load(Ordering::Relaxed),
+            error: self.levels[4].load(Ordering::Relaxed),
+            targets,
+            dropped: self.dropped.load(Ordering::Relaxed),
+            overflowed: self.overflowed.load(Ordering::Relaxed),
+            appender_errors: self.appender_errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn level_index(level: &Level) -> usize {
+    match *level {
+        Level::TRACE => 0,
+        Level::DEBUG => 1,
+        Level::INFO => 2,
+        Level::WARN => 3,
+        Level::ERROR => 4,
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Default)]
+pub struct TelemetrySnapshot {
+    pub trace: u64,
+    pub debug: u64,
+    pub info: u64,
+    pub warn: u64,
+    pub error: u64,
+    /// Busiest targets with their event counts, in descending order.
+    pub targets: Vec<(String, u64)>,
+    /// Events that passed filtering but were not written by any appender.
+    pub dropped: u64,
+    /// Writes rejected because an appender's queue was full, see
+    /// [`NonBlockingAppender`](crate::appender::non_blocking::NonBlockingAppender).
+    pub overflowed: u64,
+    pub appender_errors: u64,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::appender::Appender;
+    use crate::pattern::Pattern;
+    use crate::testing::{CaptureAppender, TestConfig};
+    use crate::ConfigurableLayer;
+    use std::io;
+    use tracing::{debug, error, info, warn, Level};
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::registry;
+
+    struct BrokenAppender(Pattern);
+
+    impl Appender for BrokenAppender {
+        fn pattern(&self) -> &Pattern {
+            &self.0
+        }
+
+        fn write(&self, _: &str) -> io::Result<()> {
+            Err(io::Error::other("disk full"))
+        }
+    }
+
+    #[test]
+    fn counts_events_by_level_and_target() {
+        let config = TestConfig::new(CaptureAppender::new()).with_max_level(Level::INFO);
+        let layer = ConfigurableLayer::new(config);
+        let telemetry = layer.telemetry();
+
+        tracing::subscriber::with_default(registry().with(layer), || {
+            info!(target: "my_app::http", "request");
+            info!(target: "my_app::http", "request");
+            warn!(target: "my_app::db", "slow query");
+            error!(target: "my_app::db", "query failed");
+            error!(target: "my_app::cache", "miss storm");
+            debug!(target: "my_app::http", "filtered out");
+        });
+
+        let snapshot = telemetry.snapshot(2);
+        assert_eq!(
+            (
+                snapshot.trace,
+                snapshot.debug,
+                snapshot.info,
+                snapshot.warn,
+                snapshot.error
+            ),
+            (0, 0, 2, 1, 2)
+        );
+        assert_eq!(
+            snapshot.targets,
+            [
+                ("my_app::db".to_string(), 2),
+                ("my_app::http".to_string(), 2)
+            ]
+        );
+        assert_eq!((snapshot.dropped, snapshot.appender_errors), (0, 0));
+    }
+
+    #[test]
+    fn counts_failed_writes() {
+        let capture = CaptureAppender::new();
+        let layer = ConfigurableLayer::new(
+            TestConfig::new(BrokenAppender(Pattern::new(vec![]))).with_appender(capture.clone()),
+        );
+        let telemetry = layer.telemetry();
+
+        tracing::subscriber::with_default(registry().with(layer), || {
+            info!("written by one of two appenders");
+        });
+
+        let layer = ConfigurableLayer::new(TestConfig::new(BrokenAppender(Pattern::new(vec![]))));
+        let broken = layer.telemetry();
+
+        tracing::subscriber::with_default(registry().with(layer), || {
+            info!("written by none");
+        });
+
+        let snapshot = telemetry.snapshot(0);
+        assert_eq!((snapshot.dropped, snapshot.appender_errors), (0, 1));
+        assert_eq!(capture.events().len(), 1);
+
+        let snapshot = broken.snapshot(0);
+        assert_eq!((snapshot.dropped, snapshot.appender_errors), (1, 1));
+    }
+}
@@ -0,0 +1,269 @@
+use crate::appender::{Appender, Record};
+use crate::config::{LayerConfig, SpanScope};
+use crate::pattern::{Pattern, PatternItem, Placeholder, PlaceholderType};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tracing::Level;
+
+/// An event recorded by [`CaptureAppender`].
+#[derive(Debug, Clone)]
+pub struct CapturedEvent {
+    /// `None` for lines written without an event, e.g. by another appender wrapping
+    /// this one.
+    pub level: Option<Level>,
+    pub target: Option<String>,
+    pub message: String,
+    pub fields: Vec<(&'static str, String)>,
+    /// The line as rendered by the appender's pattern.
+    pub rendered: String,
+}
+
+impl CapturedEvent {
+    /// First value recorded for `name`.
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn matches(&self, level: &Level, target: &str, msg_contains: &str) -> bool {
+        let target_matches = self.target.as_deref().is_some_and(|i| {
+            i == target || i.strip_prefix(target).is_some_and(|i| i.starts_with("::"))
+        });
+
+        self.level.as_ref() == Some(level) && target_matches && self.message.contains(msg_contains)
+    }
+}
+
+/// Appender that keeps events in memory so tests can assert on what was logged.
+///
+/// Clones share the captured events, so one clone can be returned from
+/// [`LayerConfig::get_appenders`](crate::config::LayerConfig::get_appenders)
+/// while the test keeps another.
+#[derive(Clone)]
+pub struct CaptureAppender {
+    pattern: Arc<Pattern>,
+    events: Arc<Mutex<Vec<CapturedEvent>>>,
+}
+
+impl CaptureAppender {
+    /// Captures events rendered with a plain `$message` pattern.
+    pub fn new() -> Self {
+        Self::with_pattern(Pattern::new(vec![PatternItem::Placeholder(
+            Placeholder::new(PlaceholderType::Message, HashMap::new(), vec![]),
+        )]))
+    }
+
+    pub fn with_pattern(pattern: Pattern) -> Self {
+        Self {
+            pattern: Arc::new(pattern),
+            events: Default::default(),
+        }
+    }
+
+    pub fn events(&self) -> Vec<CapturedEvent> {
+        self.events.lock().unwrap().clone()
+    }
+
+    pub fn clear(&self) {
+        self.events.lock().unwrap().clear();
+    }
+
+    /// Whether an event with `level` was logged to `target` (or a module below it)
+    /// with a message containing `msg_contains`.
+    pub fn logged(&self, level: Level, target: &str, msg_contains: &str) -> bool {
+        self.events
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|i| i.matches(&level, target, msg_contains))
+    }
+
+    #[track_caller]
+    pub fn assert_logged(&self, level: Level, target: &str, msg_contains: &str) {
+        if !self.logged(level, target, msg_contains) {
+            panic!(
+                "expected a {} event for `{}` containing {:?}, captured:\n{}",
+                level,
+                target,
+                msg_contains,
+                self.dump()
+            );
+        }
+    }
+
+    #[track_caller]
+    pub fn assert_not_logged(&self, level: Level, target: &str, msg_contains: &str) {
+        if self.logged(level, target, msg_contains) {
+            panic!(
+                "unexpected {} event for `{}` containing {:?}, captured:\n{}",
+                level,
+                target,
+                msg_contains,
+                self.dump()
+            );
+        }
+    }
+
+    fn dump(&self) -> String {
+        self.events
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|i| match (&i.level, &i.target) {
+                (Some(level), Some(target)) => format!("  {} {}: {}", level, target, i.rendered),
+                _ => format!("  {}", i.rendered),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl Default for CaptureAppender {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Appender for CaptureAppender {
+    fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+
+    fn write(&self, value: &str) -> std::io::Result<()> {
+        self.events.lock().unwrap().push(CapturedEvent {
+            level: None,
+            target: None,
+            message: value.to_string(),
+            fields: vec![],
+            rendered: value.to_string(),
+        });
+
+        Ok(())
+    }
+
+    fn write_record(&self, record: &Record<'_>, value: &str) -> std::io::Result<()> {
+        let fields = record
+            .fields()
+            .values()
+            .flat_map(|(key, values)| values.iter().map(move |v| (key, v.to_string())))
+            .collect();
+
+        self.events.lock().unwrap().push(CapturedEvent {
+            level: Some(*record.level()),
+            target: Some(record.target().to_string()),
+            message: record.fields().message().to_string(),
+            fields,
+            rendered: value.to_string(),
+        });
+
+        Ok(())
+    }
+}
+
+type SpanRoute = Box<dyn Fn(&SpanScope<'_>) -> Vec<Box<dyn Appender>> + Send + Sync>;
+
+/// Config sending every enabled event to a fixed set of appenders, to set up a layer in
+/// tests without writing a [`LayerConfig`] each time.
+///
+/// ```ignore
+/// let capture = CaptureAppender::new();
+/// let layer = ConfigurableLayer::new(TestConfig::new(capture.clone()));
+/// ```
+pub struct TestConfig {
+    appenders: Vec<Arc<dyn Appender + Send + Sync>>,
+    max_level: Level,
+    span_route: Option<SpanRoute>,
+}
+
+impl TestConfig {
+    /// Enables all events and writes them to `appender`.
+    pub fn new<A: Appender + Send + Sync + 'static>(appender: A) -> Self {
+        Self {
+            appenders: vec![Arc::new(appender)],
+            max_level: Level::TRACE,
+            span_route: None,
+        }
+    }
+
+    pub fn with_appender<A: Appender + Send + Sync + 'static>(mut self, appender: A) -> Self {
+        self.appenders.push(Arc::new(appender));
+        self
+    }
+
+    /// Enables only events at `level` or more severe.
+    pub fn with_max_level(mut self, level: Level) -> Self {
+        self.max_level = level;
+        self
+    }
+
+    /// Routes events by their spans, adding the appenders `route` returns to the fixed ones.
+    pub fn with_span_route<F>(mut self, route: F) -> Self
+    where
+        F: Fn(&SpanScope<'_>) -> Vec<Box<dyn Appender>> + Send + Sync + 'static,
+    {
+        self.span_route = Some(Box::new(route));
+        self
+    }
+}
+
+impl LayerConfig for TestConfig {
+    fn enabled(&self, level: &Level, _: &str) -> bool {
+        *level <= self.max_level
+    }
+
+    fn get_appenders(&self, _: &Level, _: &str) -> Vec<Box<dyn Appender>> {
+        self.appenders
+            .iter()
+            .map(|i| Box::new(i.clone()) as Box<dyn Appender>)
+            .collect()
+    }
+
+    fn routes_by_span(&self) -> bool {
+        self.span_route.is_some()
+    }
+
+    fn get_span_appenders(
+        &self,
+        level: &Level,
+        module: &str,
+        spans: &SpanScope<'_>,
+    ) -> Vec<Box<dyn Appender>> {
+        let mut appenders = self.get_appenders(level, module);
+        if let Some(route) = &self.span_route {
+            appenders.extend(route(spans));
+        }
+
+        appenders
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::appender::Appender;
+    use crate::testing::{CaptureAppender, TestConfig};
+    use crate::ConfigurableLayer;
+    use tracing::{info, warn, Level};
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::registry;
+
+    #[test]
+    fn captures_events() {
+        let capture = CaptureAppender::new();
+        let subscriber = registry().with(ConfigurableLayer::new(TestConfig::new(capture.clone())));
+
+        tracing::subscriber::with_default(subscriber, || {
+            info!(user = "alice", "user logged in");
+            warn!(target: "my_app::db", "slow query");
+        });
+        capture.write("raw line").unwrap();
+
+        capture.assert_logged(Level::INFO, "tracing_configurable", "logged in");
+        capture.assert_logged(Level::WARN, "my_app", "slow");
+        capture.assert_not_logged(Level::ERROR, "my_app", "slow");
+        capture.assert_not_logged(Level::INFO, "", "raw line");
+        assert_eq!(capture.events()[0].field("user"), Some("alice"));
+        assert_eq!(capture.events()[2].level, None);
+    }
+}